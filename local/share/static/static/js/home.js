@@ -1,4 +1,29 @@
 $(function() {
+  function refreshCounts() {
+    $.getJSON('/unread/', function(counts) {
+      $('span.unread-count').each(function() {
+        var count = counts[$(this).data('name')];
+        $(this).text(count ? count : '');
+      });
+      $('ul.left-menu > li').each(function() {
+        var total = 0;
+        $(this).find('span.unread-count').each(function() {
+          total += parseInt($(this).text()) || 0;
+        });
+        $(this).find('span.unread-total').text(total ? total : '');
+      });
+    });
+  }
+
+  // headings and names may hold a - or characters urls treat specially, so they are kept in
+  // their own attributes and encoded
+  function feedLink(link) {
+    return '/'+encodeURIComponent($(link).attr('data-heading'))+'/'+encodeURIComponent($(link).attr('data-name'))+'/';
+  }
+  function headingLink(link) {
+    return '/'+encodeURIComponent($(link).closest('a.class-link').attr('data-heading'))+'/';
+  }
+
   $('ul.left-menu li > ul').hide();
   $('ul.left-menu li').click(function(e){
     e.stopPropagation();
@@ -7,42 +32,216 @@ $(function() {
   $('a.feed-link').click(function(e){
     e.preventDefault();
     $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
-    var title = $(this).attr('title');
-    var parts = title.split('-');
-    if (parts.length >=2){
-      $.get('/'+parts[0]+'/'+parts[1]+'/', function(data) {
-        $('.viewpane').html(data);
-        $('.viewpane-view').find('img').addClass('img-fluid');
-      }).fail(function() {
-          console.log("Failed to fetch content from path");
-        });
-    }
+    $.get(feedLink(this), function(data) {
+      $('.viewpane').html(data);
+      $('.viewpane-view').find('img').addClass('img-fluid');
+    }).fail(function() {
+        console.log("Failed to fetch content from path");
+      });
   });
   $('a.feed-link').bind("contextmenu", function(e){
     e.preventDefault();
     $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
-    var title = $(this).attr('title');
-    var parts = title.split('-');
-    if (parts.length >=2){
-      $.get('/'+'force'+'/'+parts[0]+'/'+parts[1]+'/', function(data) {
-        $('.viewpane').html(data);
-        $('.viewpane-view').find('img').addClass('img-fluid');
-      }).fail(function() {
-          console.log("Failed to fetch content from path");
-        });
-    }
+    $.get('/force'+feedLink(this), function(data) {
+      $('.viewpane').html(data);
+      $('.viewpane-view').find('img').addClass('img-fluid');
+    }).fail(function() {
+        console.log("Failed to fetch content from path");
+      });
   });
 
   $('a.class-link').bind("contextmenu", function(e){
     e.preventDefault();
     $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
-    var title = $(this).attr('title');
-    console.log('/'+title+'/')
-    $.get('/'+title+'/', function(data) {
+    $.get('/force'+headingLink(this), function(data) {
+      $('.viewpane').html(data);
+      $('.viewpane-view').find('img').addClass('img-fluid');
+    }).fail(function() {
+        console.log("Failed to fetch content from path");
+      });
+  });
+
+  $('.mark-heading').click(function(e){
+    e.preventDefault();
+    e.stopPropagation();
+    $.post('/read'+headingLink(this), function() {
+      refreshCounts();
+    }).fail(function() {
+        console.log("Failed to mark heading as read");
+      });
+  });
+
+  $('.viewpane').on('click', 'a.mark-read', function(e){
+    e.preventDefault();
+    var btn = $(this);
+    $.post('/read/item/'+btn.data('key')+'/', function() {
+      btn.closest('.card').removeClass('unread');
+      btn.remove();
+      refreshCounts();
+    }).fail(function() {
+        console.log("Failed to mark item as read");
+      });
+  });
+  $('.viewpane').on('click', 'a.mark-read-all', function(e){
+    e.preventDefault();
+    var view = $(this).closest('.card').nextAll('.viewpane-view').first();
+    $.post($(this).data('path'), function() {
+      view.find('.card.unread').removeClass('unread');
+      view.find('a.mark-read').remove();
+      refreshCounts();
+    }).fail(function() {
+        console.log("Failed to mark feed as read");
+      });
+  });
+  $('.viewpane').on('click', 'a.star', function(e){
+    e.preventDefault();
+    var btn = $(this);
+    var starred = String(btn.data('starred')) == 'true';
+    $.post('/'+(starred ? 'unstar' : 'star')+'/'+btn.data('key')+'/', function() {
+      btn.data('starred', !starred);
+      btn.html('<i class="fa-'+(starred ? 'regular' : 'solid')+' fa-star"></i>');
+    }).fail(function() {
+        console.log("Failed to star item");
+      });
+  });
+
+  $('a.starred-link').click(function(e){
+    e.preventDefault();
+    $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
+    $.get('/starred/', function(data) {
+      $('.viewpane').html(data);
+      $('.viewpane-view').find('img').addClass('img-fluid');
+    }).fail(function() {
+        console.log("Failed to fetch content from path");
+      });
+  });
+
+  $('form.search-form').submit(function(e){
+    e.preventDefault();
+    if (!$(this).find('input[name=q]').val().trim()) {
+      return;
+    }
+    $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
+    $.get('/search/', $(this).serialize(), function(data) {
+      $('.viewpane').html(data);
+      $('.viewpane-view').find('img').addClass('img-fluid');
+    }).fail(function() {
+        console.log("Failed to fetch search results");
+      });
+  });
+
+  $('.heading-timeline').click(function(e){
+    e.preventDefault();
+    e.stopPropagation();
+    $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
+    $.get('/timeline'+headingLink(this), function(data) {
       $('.viewpane').html(data);
       $('.viewpane-view').find('img').addClass('img-fluid');
     }).fail(function() {
         console.log("Failed to fetch content from path");
       });
   });
+
+  $('.heading-refresh').click(function(e){
+    e.preventDefault();
+    e.stopPropagation();
+    $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
+    $.get('/force'+headingLink(this), function(data) {
+      $('.viewpane').html(data);
+      $('.viewpane-view').find('img').addClass('img-fluid');
+      refreshCounts();
+    }).fail(function() {
+        console.log("Failed to fetch content from path");
+      });
+  });
+
+  function showManage() {
+    $('.viewpane').html('<div class="fa-2x d-flex justify-content-center align-items-center"><i class="fa-solid fa-volleyball fa-bounce" style="color:#7A306C"></i></div>');
+    $.get('/manage/', function(data) {
+      $('.viewpane').html(data);
+    }).fail(function() {
+        console.log("Failed to fetch feed list");
+      });
+  }
+  // the sidebar lists feeds too, so reload the page and come back to the feed list
+  function sendEdit(method, path, body) {
+    $.ajax({
+      url: path,
+      method: method,
+      contentType: 'application/json',
+      data: body ? JSON.stringify(body) : undefined
+    }).done(function() {
+      window.location.hash = 'manage';
+      window.location.reload();
+    }).fail(function(xhr) {
+      $('.manage-error').text(xhr.responseText || 'Failed to save feeds').removeClass('d-none');
+    });
+  }
+  function feedPath(input) {
+    return '/manage/'+encodeURIComponent($(input).closest('tr.feed-row').data('name'))+'/';
+  }
+
+  $('a.manage-link').click(function(e){
+    e.preventDefault();
+    showManage();
+  });
+  if (window.location.hash == '#manage') {
+    showManage();
+  }
+
+  $('.viewpane').on('submit', 'form.feed-add', function(e){
+    e.preventDefault();
+    var form = $(this);
+    sendEdit('POST', '/manage/add/', {
+      heading: form.find('input[name=heading]').val(),
+      name: form.find('input[name=name]').val(),
+      link: form.find('input[name=link]').val()
+    });
+  });
+  $('.viewpane').on('submit', 'form.feed-discover', function(e){
+    e.preventDefault();
+    var list = $('.feed-candidates').empty();
+    $('.manage-error').addClass('d-none');
+    $.getJSON('/manage/discover/', $(this).serialize(), function(found) {
+      if (!found.length) {
+        list.append($('<li class="list-group-item text-body-secondary">').text('No feeds found'));
+      }
+      $.each(found, function(_, feed) {
+        var item = $('<li class="list-group-item list-group-item-action feed-candidate">')
+          .data('feed', feed)
+          .append($('<span class="badge text-bg-light me-2">').text(feed.format))
+          .append($('<span class="me-2">').text(feed.title))
+          .append($('<small class="text-body-secondary text-break">').text(feed.link));
+        list.append(item);
+      });
+    }).fail(function(xhr) {
+      $('.manage-error').text(xhr.responseText || 'Failed to look for feeds').removeClass('d-none');
+    });
+  });
+  // picking a candidate fills the add form, the heading is left to the user
+  $('.viewpane').on('click', 'li.feed-candidate', function(){
+    var feed = $(this).data('feed');
+    var form = $('form.feed-add');
+    form.find('input[name=link]').val(feed.link);
+    if (!form.find('input[name=name]').val()) {
+      form.find('input[name=name]').val(feed.title);
+    }
+    form.find('input[name=heading]').focus();
+  });
+  $('.viewpane').on('change', 'input.feed-rename', function(){
+    sendEdit('POST', feedPath(this)+'rename/', {name: $(this).val()});
+  });
+  $('.viewpane').on('change', 'input.feed-move', function(){
+    sendEdit('POST', feedPath(this)+'move/', {heading: $(this).val()});
+  });
+  $('.viewpane').on('change', 'input.feed-archive', function(){
+    sendEdit('POST', feedPath(this)+'archive/', {archive: $(this).is(':checked')});
+  });
+  $('.viewpane').on('click', 'a.feed-delete', function(e){
+    e.preventDefault();
+    var name = $(this).closest('tr.feed-row').data('name');
+    if (confirm('Delete '+name+'?')) {
+      sendEdit('DELETE', feedPath(this));
+    }
+  });
 });
@@ -0,0 +1,62 @@
+use crate::{
+    model::init::{self, Settings},
+    read_rustls_config,
+    view::compose::View,
+};
+use eyre::{eyre, Report, WrapErr};
+use std::{
+    fs::{remove_file, File},
+    path::{Path, PathBuf},
+    process,
+};
+
+// validates everything the server would load at startup, reporting every problem
+// instead of stopping at the first one
+pub fn run(config_file: Option<String>, overrides: &Settings, certificate: Option<String>) {
+    let mut problems = Vec::new();
+
+    match init::load(config_file, overrides) {
+        Ok((clientbag, serverbag)) => {
+            problems.extend(View::check(&clientbag.templatedir));
+            match serverbag.archivedir {
+                Some(dir) => {
+                    if let Err(e) = writable(&dir) {
+                        problems.push(e);
+                    }
+                }
+                None if !serverbag.archive_lst.is_empty() => problems.push(eyre!(
+                    "Feeds {} ask to be archived but no archive dir is set",
+                    serverbag.archive_lst.join(", ")
+                )),
+                None => {}
+            }
+        }
+        Err(e) => problems.push(e),
+    }
+
+    if let Some(dir) = certificate {
+        if let Err(e) = read_rustls_config(&PathBuf::from(dir)) {
+            problems.push(e);
+        }
+    }
+
+    if problems.is_empty() {
+        println!("Config ok");
+    } else {
+        for e in &problems {
+            eprintln!("error: {e:#}");
+        }
+        eprintln!("{} problem(s) found", problems.len());
+        process::exit(-1);
+    }
+}
+
+fn writable(dir: &Path) -> Result<(), Report> {
+    if !dir.is_dir() {
+        return Err(eyre!("Archive dir {} is not a directory", dir.display()));
+    }
+    let probe = dir.join(".alereader-check");
+    File::create(&probe)
+        .wrap_err_with(|| format!("Archive dir {} is not writable", dir.display()))?;
+    remove_file(&probe).wrap_err_with(|| format!("Failed to clean up {}", probe.display()))
+}
@@ -0,0 +1,60 @@
+use crate::model::{
+    init::{self, Settings},
+    opml,
+};
+use std::{fs::read_to_string, process};
+
+// merges the feeds of an opml file into the config file, feeds whose name is taken or that
+// would not make a working url are skipped
+pub fn run(config_file: Option<String>, overrides: &Settings, opml_file: String) {
+    let file = init::resolve(config_file);
+    let mut doc = match init::document(&file) {
+        Ok(doc) => doc,
+        Err(e) => fail(e),
+    };
+    let mut config = match init::config(&doc) {
+        Ok(config) => config,
+        Err(e) => fail(e),
+    };
+    let feeds = match read_to_string(&opml_file) {
+        Ok(text) => match opml::read(&text) {
+            Ok(feeds) => feeds,
+            Err(e) => fail(e.wrap_err(format!("Failed to parse {opml_file}"))),
+        },
+        Err(e) => fail(eyre::eyre!("Failed to read {opml_file}: {e}")),
+    };
+
+    let (mut added, mut skipped) = (0, 0);
+    for feed in feeds {
+        if config.feeds.iter().any(|f| f.name == feed.name) {
+            println!("Skipping {}, a feed with that name exists", feed.name);
+            skipped += 1;
+        } else if let Err(e) = init::check(&feed) {
+            println!("Skipping {}, {e:#}", feed.name);
+            skipped += 1;
+        } else {
+            println!("Adding {} under {}", feed.name, feed.heading);
+            if let Err(e) = init::push_feed(&mut doc, &feed) {
+                fail(e);
+            }
+            config.feeds.push(feed);
+            added += 1;
+        }
+    }
+
+    if added > 0 {
+        // the merged config has to load before it replaces the file
+        if let Err(e) = init::build(config, overrides) {
+            fail(e.wrap_err("The imported feeds would not load, nothing was written"));
+        }
+        if let Err(e) = init::save(&file, &doc) {
+            fail(e);
+        }
+    }
+    println!("Imported {added} feeds into {file}, skipped {skipped}");
+}
+
+fn fail(e: eyre::Report) -> ! {
+    eprintln!("error: {e:#}");
+    process::exit(-1);
+}
@@ -1,39 +1,152 @@
+mod check;
+mod import;
 mod model;
+mod reload;
 mod view;
 
 use actix_files::Files;
 use actix_web::{web, App, HttpServer};
-use clap::Parser;
-use crossbeam::channel::{unbounded, Receiver, Sender};
+use clap::{Args, Parser, Subcommand};
 use env_logger::Env;
+use eyre::{eyre, Result, WrapErr};
 use log::{debug, error, info};
 use model::{
     fetch::{DataPkt, Fetcher},
-    init,
+    init::{self, Settings},
+    schedule,
 };
 use rustls::{pki_types::PrivateKeyDer, ServerConfig};
 use rustls_pemfile::{certs, pkcs8_private_keys};
-use std::{fs::File, io::BufReader, path::PathBuf, sync::Arc, thread};
-use tokio::runtime::Builder;
-use view::client::{getfeed, getforcefeed, getfull, gethome, Controller};
+use std::{
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, SocketAddr},
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+use tokio::{
+    runtime::Builder,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+use view::api::{apifeeditems, apifeeds, apiheadingitems, apiheadings, apiitems};
+use view::client::{
+    addfeed, archivefeed, deletefeed, discoverfeeds, getfeed, getforcefeed, getforcefull, getfull,
+    gethome, getmanage, getopml, getsearch, getstarred, gettimeline, getunread, movefeed, readfeed,
+    readheading, readitem, renamefeed, staritem, unstaritem, Controller,
+};
+use view::publish::{getheadingatom, getheadingjson, getheadingrss};
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    after_help = "Each setting is taken from the first of: its command line flag, its ALEREADER_* \
+environment variable, the [settings] table of the config file, the built-in default."
+)]
 struct CmdVars {
-    #[arg(short, long, value_name = "FILE")]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        global = true,
+        env = "ALEREADER_CONFIG_FILE"
+    )]
     config_file: Option<String>,
-    #[arg(short, long, default_value_t = 7878)]
+    #[arg(short, long, default_value_t = 7878, env = "ALEREADER_PORT")]
     port: u16,
+    /// Address to listen on, repeatable: ip, ip:port, [ipv6]:port or unix:path [default: 0.0.0.0]
+    #[arg(
+        short,
+        long,
+        value_name = "ADDR",
+        env = "ALEREADER_BIND",
+        value_delimiter = ','
+    )]
+    bind: Vec<String>,
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: Option<u8>,
-    #[arg(short = 'k', long, value_name = "CERT_BASE_DIR")]
+    #[arg(
+        short = 'k',
+        long,
+        value_name = "CERT_BASE_DIR",
+        global = true,
+        env = "ALEREADER_CERTIFICATE"
+    )]
     certificate: Option<String>,
+    #[command(flatten)]
+    overrides: Overrides,
+}
+
+// one flag per [settings] key
+#[derive(Args)]
+struct Overrides {
+    #[arg(long, value_name = "DIR", global = true, env = "ALEREADER_TEMPLATE")]
+    template: Option<PathBuf>,
+    #[arg(long, value_name = "DIR", global = true, env = "ALEREADER_ARCHIVE")]
+    archive: Option<PathBuf>,
+    #[arg(long, value_name = "FILE", global = true, env = "ALEREADER_DATABASE")]
+    database: Option<PathBuf>,
+    #[arg(long, global = true, env = "ALEREADER_USERAGENT")]
+    useragent: Option<String>,
+    #[arg(
+        long,
+        value_name = "THREADS",
+        global = true,
+        env = "ALEREADER_DOWNLOAD"
+    )]
+    download: Option<usize>,
+    #[arg(long, value_name = "WORKERS", global = true, env = "ALEREADER_CLIENTS")]
+    clients: Option<usize>,
+    #[arg(
+        long,
+        value_name = "FEEDS",
+        global = true,
+        env = "ALEREADER_CACHE_LIMIT"
+    )]
+    cache_limit: Option<usize>,
+    #[arg(long, value_name = "SECS", global = true, env = "ALEREADER_REFRESH")]
+    refresh: Option<u64>,
+    #[arg(long, value_name = "SECS", global = true, env = "ALEREADER_DEADLINE")]
+    deadline: Option<u64>,
+}
+
+impl From<Overrides> for Settings {
+    fn from(o: Overrides) -> Self {
+        Settings {
+            template: o.template,
+            archive: o.archive,
+            database: o.database,
+            useragent: o.useragent,
+            download: o.download,
+            clients: o.clients,
+            cache_limit: o.cache_limit,
+            refresh: o.refresh,
+            deadline: o.deadline,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Validate the config, templates, archive dir and TLS files without serving
+    Check,
+    /// Add the feeds of an OPML file to the config file
+    Import {
+        #[arg(value_name = "OPML_FILE")]
+        file: String,
+    },
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let cli = CmdVars::parse();
     let port = cli.port;
+    let overrides = Settings::from(cli.overrides);
 
     match cli.debug {
         Some(0) => env_logger::Builder::from_env(Env::default().default_filter_or("off")).init(),
@@ -43,114 +156,315 @@ async fn main() -> std::io::Result<()> {
         _ => env_logger::Builder::from_env(Env::default().default_filter_or("off")).init(),
     }
 
+    match cli.command {
+        Some(Command::Check) => {
+            check::run(cli.config_file, &overrides, cli.certificate);
+            return Ok(());
+        }
+        Some(Command::Import { file }) => {
+            import::run(cli.config_file, &overrides, file);
+            return Ok(());
+        }
+        None => {}
+    }
+
     info!("Server init");
 
-    let (clientbag, serverbag) = init::init(cli.config_file);
+    let config_file = init::resolve(cli.config_file);
+    let (clientbag, serverbag) = init::init(Some(config_file.clone()), &overrides);
 
     let model_runtime = Builder::new_multi_thread()
-        .worker_threads(serverbag.download.clone())
+        .worker_threads(serverbag.download)
         .thread_name("alereader-downloader-runtime")
         .enable_all()
         .build()
         .unwrap();
-    let (model_tx, model_rx): (Sender<DataPkt>, Receiver<DataPkt>) = unbounded();
+    let (model_tx, mut model_rx): (UnboundedSender<DataPkt>, UnboundedReceiver<DataPkt>) =
+        unbounded_channel();
+
+    let downman = Arc::new(Fetcher::new(
+        serverbag,
+        config_file.clone(),
+        overrides.clone(),
+    ));
+
+    // run scheduler
+    model_runtime.spawn(schedule::run(Arc::clone(&downman)));
+    let model_handle = model_runtime.handle().clone();
+    let reloadref = Arc::clone(&downman);
 
     // run downloader
     thread::spawn(move || {
-        let downman = Arc::new(Fetcher::new(serverbag));
-        loop {
-            if let Ok(name) = model_rx.recv() {
-                let downref = Arc::clone(&downman);
-                if let DataPkt::Request(s, t) = name {
-                    model_runtime.spawn(async move {
-                        downref.get(DataPkt::Request(s, t)).await;
-                    });
-                } else if let DataPkt::ForceRequest(s, t) = name {
-                    model_runtime.spawn(async move {
-                        downref.forceget(DataPkt::ForceRequest(s, t)).await;
-                    });
-                }
+        while let Some(name) = model_rx.blocking_recv() {
+            let downref = Arc::clone(&downman);
+            if let DataPkt::Request(s, t) = name {
+                model_runtime.spawn(async move {
+                    downref.get(DataPkt::Request(s, t)).await;
+                });
+            } else if let DataPkt::ForceRequest(s, t) = name {
+                model_runtime.spawn(async move {
+                    downref.forceget(DataPkt::ForceRequest(s, t)).await;
+                });
+            } else if let DataPkt::Read(s, t) = name {
+                model_runtime.spawn(async move {
+                    downref.mark(DataPkt::Read(s, t)).await;
+                });
+            } else if let DataPkt::Unread(t) = name {
+                model_runtime.spawn(async move {
+                    downref.unread(DataPkt::Unread(t)).await;
+                });
+            } else if let DataPkt::Star(k, s, t) = name {
+                model_runtime.spawn(async move {
+                    downref.star(DataPkt::Star(k, s, t)).await;
+                });
+            } else if let DataPkt::Starred(t) = name {
+                model_runtime.spawn(async move {
+                    downref.starred(DataPkt::Starred(t)).await;
+                });
+            } else if let DataPkt::Search(q, f, t) = name {
+                model_runtime.spawn(async move {
+                    downref.search(DataPkt::Search(q, f, t)).await;
+                });
+            } else if let DataPkt::Timeline(h, n, t) = name {
+                model_runtime.spawn(async move {
+                    downref.timeline(DataPkt::Timeline(h, n, t)).await;
+                });
+            } else if let DataPkt::Export(t) = name {
+                model_runtime.spawn(async move {
+                    downref.export(DataPkt::Export(t)).await;
+                });
+            } else if let DataPkt::Subscriptions(t) = name {
+                model_runtime.spawn(async move {
+                    downref.subscriptions(DataPkt::Subscriptions(t)).await;
+                });
+            } else if let DataPkt::Edit(e, t) = name {
+                model_runtime.spawn(async move {
+                    downref.edit(DataPkt::Edit(e, t)).await;
+                });
+            } else if let DataPkt::Items(q, t) = name {
+                model_runtime.spawn(async move {
+                    downref.records(DataPkt::Items(q, t)).await;
+                });
+            } else if let DataPkt::Discover(u, t) = name {
+                model_runtime.spawn(async move {
+                    downref.discover(DataPkt::Discover(u, t)).await;
+                });
             }
         }
     });
 
     // setup client
-    let client = clientbag.clients.clone();
+    let client = clientbag.clients;
     let mut templatedir = clientbag.templatedir.clone();
     templatedir.push("static");
     let templatedir = templatedir.to_str().unwrap().to_string();
     let controller = web::Data::new(Controller::new(clientbag, model_tx));
 
+    // run config reloader
+    model_handle.spawn(reload::watch(
+        config_file,
+        overrides,
+        reloadref,
+        controller.clone(),
+    ));
+
     //get rustconfig
     let config = load_rustls_config(cli.certificate);
-    match config {
-        Some(config) => {
-            HttpServer::new(move || {
-                App::new()
-                    .app_data(controller.clone())
-                    .service(Files::new("/static", &templatedir))
-                    .service(gethome)
-                    .service(getfull)
-                    .service(getfeed)
-                    .service(getforcefeed)
-            })
-            .bind_rustls_0_23(("0.0.0.0", port), config)?
-            .workers(client)
-            .run()
-            .await
-        }
-        None => {
-            HttpServer::new(move || {
-                App::new()
-                    .app_data(controller.clone())
-                    .service(Files::new("/static", &templatedir))
-                    .service(gethome)
-                    .service(getfull)
-                    .service(getfeed)
-                    .service(getforcefeed)
-            })
-            .bind(("0.0.0.0", port))?
-            .workers(client)
-            .run()
-            .await
+    let listeners = match listeners(&cli.bind, port) {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            error!("{e:#}");
+            std::process::exit(-1);
         }
+    };
+
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .app_data(controller.clone())
+            .service(Files::new("/static", &templatedir))
+            .service(gethome)
+            .service(getopml)
+            .service(getmanage)
+            .service(discoverfeeds)
+            .service(addfeed)
+            .service(renamefeed)
+            .service(movefeed)
+            .service(archivefeed)
+            .service(deletefeed)
+            .service(getunread)
+            .service(readitem)
+            .service(readfeed)
+            .service(readheading)
+            .service(staritem)
+            .service(unstaritem)
+            .service(getstarred)
+            .service(getsearch)
+            .service(gettimeline)
+            .service(apiheadings)
+            .service(apifeeds)
+            .service(apiitems)
+            .service(apiheadingitems)
+            .service(apifeeditems)
+            .service(getheadingrss)
+            .service(getheadingatom)
+            .service(getheadingjson)
+            .service(getforcefull)
+            .service(getfull)
+            .service(getfeed)
+            .service(getforcefeed)
+    })
+    .workers(client);
+    for listener in listeners {
+        server = match (listener, &config) {
+            (Listener::Tcp(addr), Some(config)) => {
+                info!("Listening on https://{addr}");
+                server.bind_rustls_0_23(addr, config.clone())?
+            }
+            (Listener::Tcp(addr), None) => {
+                info!("Listening on http://{addr}");
+                server.bind(addr)?
+            }
+            (Listener::Unix(path), _) => {
+                // binding replaces whatever is at path, which is only fine for a stale socket
+                if let Ok(meta) = std::fs::metadata(&path) {
+                    if !meta.file_type().is_socket() {
+                        error!("{} exists and is not a socket", path.display());
+                        std::process::exit(-1);
+                    }
+                }
+                info!("Listening on unix:{}, without tls", path.display());
+                server.bind_uds(path)?
+            }
+        };
     }
+    server.run().await
+}
+
+#[derive(Debug, PartialEq)]
+enum Listener {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+// turns each --bind value into a listener, addresses without a port get the --port one
+fn listeners(binds: &[String], port: u16) -> Result<Vec<Listener>> {
+    if binds.is_empty() {
+        return Ok(vec![Listener::Tcp(SocketAddr::from(([0, 0, 0, 0], port)))]);
+    }
+    binds
+        .iter()
+        .map(|bind| {
+            if let Some(path) = bind.strip_prefix("unix:") {
+                Ok(Listener::Unix(PathBuf::from(path)))
+            } else if let Ok(addr) = bind.parse::<SocketAddr>() {
+                Ok(Listener::Tcp(addr))
+            } else if let Ok(ip) = bind.trim_matches(['[', ']']).parse::<IpAddr>() {
+                Ok(Listener::Tcp(SocketAddr::new(ip, port)))
+            } else {
+                Err(eyre!(
+                    "Bad bind address {bind}, expected an ip, ip:port, [ipv6]:port or unix:path"
+                ))
+            }
+        })
+        .collect()
 }
 
 fn load_rustls_config(base_dir: Option<String>) -> Option<rustls::ServerConfig> {
     match base_dir {
-        Some(dir) => {
-            rustls::crypto::aws_lc_rs::default_provider()
-                .install_default()
-                .unwrap();
-            // init server config builder with safe defaults
-            let config = ServerConfig::builder().with_no_client_auth();
-
-            let base = PathBuf::from(dir);
-            let mut cert_loc = base.clone();
-            cert_loc.push("cert.pem");
-            let mut key_loc = base.clone();
-            key_loc.push("key.pem");
-            // load TLS key/cert files
-            let cert_file = &mut BufReader::new(File::open(cert_loc).unwrap());
-            let key_file = &mut BufReader::new(File::open(key_loc).unwrap());
-
-            // convert files to key/cert objects
-            let cert_chain = certs(cert_file).collect::<Result<Vec<_>, _>>().unwrap();
-            let mut keys = pkcs8_private_keys(key_file)
-                .map(|key| key.map(PrivateKeyDer::Pkcs8))
-                .collect::<Result<Vec<_>, _>>()
-                .unwrap();
-            // exit if no keys could be parsed
-            if keys.is_empty() {
-                error!("Could not locate PKCS 8 private keys.");
+        Some(dir) => match read_rustls_config(&PathBuf::from(dir)) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                error!("{e:#}");
                 std::process::exit(-1);
             }
-            Some(config.with_single_cert(cert_chain, keys.remove(0)).unwrap())
-        }
+        },
         None => {
             debug!("No base dir defined, won't be using tls");
             None
         }
     }
 }
+
+pub fn read_rustls_config(base: &Path) -> Result<rustls::ServerConfig> {
+    // a provider may already be installed, which is fine
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    // init server config builder with safe defaults
+    let config = ServerConfig::builder().with_no_client_auth();
+
+    let cert_loc = base.join("cert.pem");
+    let key_loc = base.join("key.pem");
+    // load TLS key/cert files
+    let cert_file = &mut BufReader::new(
+        File::open(&cert_loc).wrap_err_with(|| format!("Failed to open {}", cert_loc.display()))?,
+    );
+    let key_file = &mut BufReader::new(
+        File::open(&key_loc).wrap_err_with(|| format!("Failed to open {}", key_loc.display()))?,
+    );
+
+    // convert files to key/cert objects
+    let cert_chain = certs(cert_file)
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err_with(|| format!("Failed to read certificates from {}", cert_loc.display()))?;
+    let mut keys = pkcs8_private_keys(key_file)
+        .map(|key| key.map(PrivateKeyDer::Pkcs8))
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err_with(|| format!("Failed to read private keys from {}", key_loc.display()))?;
+    if keys.is_empty() {
+        return Err(eyre!(
+            "Could not locate PKCS 8 private keys in {}",
+            key_loc.display()
+        ));
+    }
+    config
+        .with_single_cert(cert_chain, keys.remove(0))
+        .wrap_err("Certificate and private key do not form a usable pair")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind(value: &str) -> Listener {
+        listeners(&[value.to_string()], 7878).unwrap().remove(0)
+    }
+
+    #[test]
+    fn binds_without_a_port_take_the_default() {
+        assert_eq!(bind("[::1]"), Listener::Tcp("[::1]:7878".parse().unwrap()));
+        assert_eq!(
+            bind("127.0.0.1"),
+            Listener::Tcp("127.0.0.1:7878".parse().unwrap())
+        );
+        // a bare ipv6 address can not carry a port, ::1:80 is the address 0:0:0:0:0:0:1:80
+        assert_eq!(
+            bind("::1:80"),
+            Listener::Tcp("[::1:80]:7878".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn binds_with_a_port_keep_it() {
+        assert_eq!(bind("[::1]:80"), Listener::Tcp("[::1]:80".parse().unwrap()));
+        assert_eq!(
+            bind("0.0.0.0:8080"),
+            Listener::Tcp("0.0.0.0:8080".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn unix_binds_are_paths() {
+        assert_eq!(
+            bind("unix:/run/alereader.sock"),
+            Listener::Unix(PathBuf::from("/run/alereader.sock"))
+        );
+    }
+
+    #[test]
+    fn no_bind_listens_everywhere() {
+        assert_eq!(
+            listeners(&[], 9000).unwrap(),
+            vec![Listener::Tcp("0.0.0.0:9000".parse().unwrap())]
+        );
+        assert!(listeners(&["localhost".to_string()], 7878).is_err());
+    }
+}
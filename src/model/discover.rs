@@ -0,0 +1,250 @@
+use crate::model::{
+    doc::Doc,
+    fetch::{self, FeedError},
+    store::Source,
+};
+use futures::future::join_all;
+use log::debug;
+use regex::Regex;
+use reqwest::{header::USER_AGENT, Client, Url};
+use serde::Serialize;
+use std::{sync::LazyLock, time::Duration};
+
+// where sites tend to keep a feed nobody links to
+const COMMON_PATHS: [&str; 7] = [
+    "/feed",
+    "/rss.xml",
+    "/atom.xml",
+    "/feed.xml",
+    "/index.xml",
+    "/feed.json",
+    "/rss",
+];
+
+const FEED_TYPES: [&str; 3] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+];
+
+// seconds each page or candidate gets to answer
+const TIMEOUT: u64 = 10;
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<(link|base)\b[^>]*>").unwrap());
+static ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)([a-z][a-z0-9_:-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub link: String,
+    pub title: String,
+    pub format: &'static str,
+}
+
+// the link a user typed, a bare host is taken to be https
+pub fn page_url(text: &str) -> Result<Url, String> {
+    let text = text.trim();
+    let text = if text.contains("://") {
+        text.to_string()
+    } else {
+        format!("https://{text}")
+    };
+    match Url::parse(&text) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url),
+        Ok(url) => Err(format!("Can not look for feeds over {}", url.scheme())),
+        Err(e) => Err(format!("{text} is not a link: {e}")),
+    }
+}
+
+// feeds that page announces or that sit at the usual paths of its site, each one fetched
+// and read to make sure it is a feed. a page that is a feed itself is the only candidate
+pub async fn find(
+    client: &Client,
+    useragent: &str,
+    page: Url,
+) -> Result<Vec<Candidate>, FeedError> {
+    let resp = client
+        .get(page.clone())
+        .header(USER_AGENT, useragent)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .send()
+        .await
+        .map_err(fetch::request_error)?;
+    if !resp.status().is_success() {
+        return Err(FeedError::Status(resp.status().as_u16()));
+    }
+    // relative links resolve against wherever the redirects ended
+    let base = resp.url().clone();
+    let txt = resp.text().await.map_err(fetch::request_error)?;
+    if let Ok(source) = fetch::parse(&txt) {
+        return Ok(vec![candidate(page.to_string(), &source)]);
+    }
+
+    let mut links = announced(&txt, &base);
+    links.extend(COMMON_PATHS.iter().filter_map(|path| base.join(path).ok()));
+    let mut seen = Vec::new();
+    links.retain(|url| {
+        let fresh = !seen.contains(url);
+        seen.push(url.clone());
+        fresh
+    });
+
+    let probes = links.into_iter().map(|url| probe(client, useragent, url));
+    let mut found: Vec<(Url, Candidate)> = Vec::new();
+    for (end, candidate) in join_all(probes).await.into_iter().flatten() {
+        // /feed often redirects to the very feed the page announced
+        if !found.iter().any(|(seen, _)| *seen == end) {
+            found.push((end, candidate));
+        }
+    }
+    Ok(found.into_iter().map(|(_, candidate)| candidate).collect())
+}
+
+// the <link rel="alternate"> feeds of an html page, resolved against its <base> if it has one
+fn announced(html: &str, page: &Url) -> Vec<Url> {
+    let mut base = page.clone();
+    let mut links = Vec::new();
+    for tag in TAG.captures_iter(html) {
+        let attrs = attributes(&tag[0]);
+        let get = |name: &str| {
+            attrs
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, val)| val.as_str())
+        };
+        if tag[1].eq_ignore_ascii_case("base") {
+            if let Some(Ok(url)) = get("href").map(|href| page.join(href)) {
+                base = url;
+            }
+            continue;
+        }
+        let alternate = get("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("alternate"))
+        });
+        let feed = get("type").is_some_and(|t| {
+            let mime = t.split(';').next().unwrap_or_default().trim();
+            FEED_TYPES.iter().any(|f| f.eq_ignore_ascii_case(mime))
+        });
+        if alternate && feed {
+            if let Some(Ok(url)) = get("href").map(|href| base.join(href.trim())) {
+                links.push(url);
+            }
+        }
+    }
+    links
+}
+
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    ATTR.captures_iter(tag)
+        .map(|c| {
+            let val = c
+                .get(2)
+                .or(c.get(3))
+                .or(c.get(4))
+                .map_or("", |m| m.as_str());
+            let val = quick_xml::escape::unescape(val).map_or(val.to_string(), |v| v.into_owned());
+            (c[1].to_string(), val)
+        })
+        .collect()
+}
+
+// the url the probe ended at, and the candidate if a feed was found there
+async fn probe(client: &Client, useragent: &str, url: Url) -> Option<(Url, Candidate)> {
+    let resp = client
+        .get(url.clone())
+        .header(USER_AGENT, useragent)
+        .timeout(Duration::from_secs(TIMEOUT))
+        .send()
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        debug!("{url} answered {}, not a feed", resp.status());
+        return None;
+    }
+    let end = resp.url().clone();
+    let txt = resp.text().await.ok()?;
+    match fetch::parse(&txt) {
+        Ok(source) => Some((end, candidate(url.to_string(), &source))),
+        Err(e) => {
+            debug!("{url} is not a feed! {e}");
+            None
+        }
+    }
+}
+
+fn candidate(link: String, source: &Source) -> Candidate {
+    let format = match source {
+        Source::Channel(_) => "rss",
+        Source::Feed(_) => "atom",
+        Source::Json(_) => "json",
+    };
+    Candidate {
+        link,
+        title: Doc::from(source).title,
+        format,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(html: &str, page: &str) -> Vec<String> {
+        announced(html, &Url::parse(page).unwrap())
+            .iter()
+            .map(Url::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn resolves_against_base_href() {
+        let html = r#"<html><head>
+<base href="https://cdn.example.org/blog/">
+<link rel="alternate" type="application/rss+xml" href="feed.xml">
+<LINK REL="Alternate" TYPE='application/atom+xml' HREF=/atom.xml>
+<link href="j.json?a=1&amp;b=2" type="application/feed+json; charset=utf-8" rel="alternate">
+</head></html>"#;
+        assert_eq!(
+            links(html, "https://example.org/"),
+            vec![
+                "https://cdn.example.org/blog/feed.xml",
+                "https://cdn.example.org/atom.xml",
+                "https://cdn.example.org/blog/j.json?a=1&b=2",
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_base_resolves_against_the_page() {
+        let html =
+            r#"<base href="/news/"><link rel="alternate" type="application/rss+xml" href="rss">"#;
+        assert_eq!(
+            links(html, "https://example.org/home/index.html"),
+            vec!["https://example.org/news/rss"]
+        );
+    }
+
+    #[test]
+    fn skips_links_that_are_not_feeds() {
+        let html = r#"<link rel="stylesheet" type="text/css" href="s.css">
+<link rel="alternate stylesheet" type="text/css" href="dark.css">
+<link rel="alternate" type="text/html" hreflang="de" href="/de/">
+<link rel="icon" type="application/rss+xml" href="odd.xml">
+<link rel="alternate" type="application/rss+xml" href="/feed">"#;
+        assert_eq!(
+            links(html, "https://example.org/page"),
+            vec!["https://example.org/feed"]
+        );
+    }
+
+    #[test]
+    fn bare_hosts_are_https() {
+        assert_eq!(
+            page_url(" example.org ").unwrap().as_str(),
+            "https://example.org/"
+        );
+        assert!(page_url("ftp://example.org/").is_err());
+    }
+}
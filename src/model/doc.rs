@@ -0,0 +1,465 @@
+use crate::model::jsonfeed::{JsonAuthor, JsonFeed, JsonItem};
+use atom_syndication::{Entry, Feed};
+use chrono::DateTime;
+use rss::{extension::dublincore::DublinCoreExtension, Channel, Item};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// one feed as everything past the parser sees it, whatever format it arrived in
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Doc {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub link: Option<String>,
+    pub links: Vec<Link>,
+    pub image: Option<String>,
+    pub updated: Option<i64>,
+    pub authors: Vec<Person>,
+    pub categories: Vec<String>,
+    pub items: Vec<Post>,
+}
+
+// one item of a Doc, dates are unix seconds
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Post {
+    pub id: String,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub links: Vec<Link>,
+    pub comments: Option<String>,
+    pub published: Option<i64>,
+    pub updated: Option<i64>,
+    pub authors: Vec<Person>,
+    pub summary: Option<String>,
+    pub content: Option<String>,
+    pub enclosures: Vec<Media>,
+    pub media: Vec<Media>,
+    pub thumbnail: Option<String>,
+    pub categories: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Link {
+    pub href: String,
+    pub rel: String,
+    pub mime_type: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Person {
+    pub name: String,
+    pub email: Option<String>,
+    pub uri: Option<String>,
+}
+
+// an enclosure or media:content, medium is image, video, audio or whatever the feed says
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Media {
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub medium: Option<String>,
+    pub length: Option<u64>,
+}
+
+impl Post {
+    // when the item was published, or last updated if it does not say. what lists are sorted by
+    pub fn date(&self) -> Option<i64> {
+        self.published.or(self.updated)
+    }
+    pub fn author(&self) -> Option<String> {
+        (!self.authors.is_empty()).then(|| {
+            self.authors
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+}
+
+impl From<&Channel> for Doc {
+    fn from(chan: &Channel) -> Self {
+        let mut links = Vec::new();
+        if !chan.link.is_empty() {
+            links.push(alternate(&chan.link));
+        }
+        if let Some(atom) = &chan.atom_ext {
+            links.extend(atom.links.iter().map(Link::from));
+        }
+        let dublin = chan.dublin_core_ext.as_ref();
+        Self {
+            title: chan.title.clone(),
+            subtitle: (!chan.description.is_empty()).then(|| chan.description.clone()),
+            link: (!chan.link.is_empty()).then(|| chan.link.clone()),
+            links,
+            image: chan
+                .image
+                .as_ref()
+                .map(|i| i.url.clone())
+                .or_else(|| chan.itunes_ext.as_ref()?.image.clone()),
+            updated: chan
+                .last_build_date
+                .as_deref()
+                .or(chan.pub_date.as_deref())
+                .or_else(|| dublin?.dates.first().map(|d| d.as_str()))
+                .and_then(timestamp),
+            authors: dublin
+                .map(|d| d.creators.iter().map(|c| person(c)).collect())
+                .unwrap_or_default(),
+            categories: categories(&chan.categories, dublin),
+            items: chan.items.iter().map(Post::from).collect(),
+        }
+    }
+}
+
+impl From<&Feed> for Doc {
+    fn from(feed: &Feed) -> Self {
+        Self {
+            title: feed.title.value.clone(),
+            subtitle: feed.subtitle.as_ref().map(|s| s.value.clone()),
+            link: alternate_href(&feed.links),
+            links: feed.links.iter().map(Link::from).collect(),
+            image: feed.logo.clone().or_else(|| feed.icon.clone()),
+            updated: Some(feed.updated.timestamp()),
+            authors: feed.authors.iter().map(Person::from).collect(),
+            categories: feed.categories.iter().map(|c| c.term.clone()).collect(),
+            items: feed.entries.iter().map(Post::from).collect(),
+        }
+    }
+}
+
+impl From<&JsonFeed> for Doc {
+    fn from(feed: &JsonFeed) -> Self {
+        let mut links = Vec::new();
+        if let Some(home) = &feed.home_page_url {
+            links.push(alternate(home));
+        }
+        if let Some(this) = &feed.feed_url {
+            links.push(Link {
+                href: this.clone(),
+                rel: "self".to_string(),
+                mime_type: Some("application/feed+json".to_string()),
+                ..Default::default()
+            });
+        }
+        Self {
+            title: feed.title.clone(),
+            subtitle: feed.description.clone(),
+            link: feed.home_page_url.clone(),
+            links,
+            image: feed.icon.clone().or_else(|| feed.favicon.clone()),
+            updated: None,
+            authors: feed.all_authors().iter().map(Person::from).collect(),
+            categories: Vec::new(),
+            items: feed.items.iter().map(Post::from).collect(),
+        }
+    }
+}
+
+impl From<&Item> for Post {
+    fn from(item: &Item) -> Self {
+        let id = match (&item.guid, &item.link) {
+            (Some(guid), _) => guid.value.clone(),
+            (None, Some(link)) => link.clone(),
+            (None, None) => fallback_id(&item.title, &item.description),
+        };
+        let dublin = item.dublin_core_ext.as_ref();
+        let authors = match &item.author {
+            Some(author) => vec![person(author)],
+            None => dublin
+                .map(|d| d.creators.iter().map(|c| person(c)).collect())
+                .unwrap_or_default(),
+        };
+        let (media, thumbnail) = media(&item.extensions);
+        Self {
+            id,
+            title: item.title.clone(),
+            link: item.link.clone(),
+            links: item.link.iter().map(|l| alternate(l)).collect(),
+            comments: item.comments.clone(),
+            published: item
+                .pub_date
+                .as_deref()
+                .or_else(|| dublin?.dates.first().map(|d| d.as_str()))
+                .and_then(timestamp),
+            updated: None,
+            authors,
+            summary: item.description.clone(),
+            content: item.content.clone(),
+            enclosures: item
+                .enclosure
+                .iter()
+                .map(|e| Media {
+                    url: e.url.clone(),
+                    medium: medium(&e.mime_type),
+                    mime_type: (!e.mime_type.is_empty()).then(|| e.mime_type.clone()),
+                    length: e.length.parse().ok(),
+                })
+                .collect(),
+            media,
+            thumbnail,
+            categories: categories(&item.categories, dublin),
+        }
+    }
+}
+
+impl From<&Entry> for Post {
+    fn from(entry: &Entry) -> Self {
+        let link = alternate_href(&entry.links);
+        let summary = entry.summary.as_ref().map(|s| s.value.clone());
+        let id = if !entry.id.is_empty() {
+            entry.id.clone()
+        } else if let Some(link) = &link {
+            link.clone()
+        } else {
+            fallback_id(&Some(entry.title.value.clone()), &summary)
+        };
+        let (media, thumbnail) = media(&entry.extensions);
+        Self {
+            id,
+            title: Some(entry.title.value.clone()),
+            link,
+            links: entry.links.iter().map(Link::from).collect(),
+            comments: entry
+                .links
+                .iter()
+                .find(|l| l.rel == "replies")
+                .map(|l| l.href.clone()),
+            published: entry.published.map(|d| d.timestamp()),
+            updated: Some(entry.updated.timestamp()),
+            authors: entry.authors.iter().map(Person::from).collect(),
+            summary,
+            content: entry.content.as_ref().and_then(|c| c.value.clone()),
+            enclosures: entry
+                .links
+                .iter()
+                .filter(|l| l.rel == "enclosure")
+                .map(|l| Media {
+                    url: l.href.clone(),
+                    medium: l.mime_type.as_deref().and_then(medium),
+                    mime_type: l.mime_type.clone(),
+                    length: l.length.as_deref().and_then(|n| n.parse().ok()),
+                })
+                .collect(),
+            media,
+            thumbnail,
+            categories: entry.categories.iter().map(|c| c.term.clone()).collect(),
+        }
+    }
+}
+
+impl From<&JsonItem> for Post {
+    fn from(item: &JsonItem) -> Self {
+        let summary = item.summary.clone();
+        let id = if !item.id.is_empty() {
+            item.id.clone()
+        } else if let Some(url) = &item.url {
+            url.clone()
+        } else {
+            fallback_id(&item.title, &summary)
+        };
+        let mut links: Vec<Link> = item.url.iter().map(|u| alternate(u)).collect();
+        if let Some(external) = &item.external_url {
+            links.push(Link {
+                href: external.clone(),
+                rel: "related".to_string(),
+                ..Default::default()
+            });
+        }
+        Self {
+            id,
+            title: item.title.clone(),
+            link: item.url.clone().or_else(|| item.external_url.clone()),
+            links,
+            comments: None,
+            published: item.date_published.as_deref().and_then(timestamp),
+            updated: item.date_modified.as_deref().and_then(timestamp),
+            authors: item.all_authors().iter().map(Person::from).collect(),
+            summary,
+            content: item
+                .content_html
+                .clone()
+                .or_else(|| item.content_text.clone()),
+            enclosures: item
+                .attachments
+                .iter()
+                .map(|a| Media {
+                    url: a.url.clone(),
+                    medium: medium(&a.mime_type),
+                    mime_type: (!a.mime_type.is_empty()).then(|| a.mime_type.clone()),
+                    length: a.size_in_bytes,
+                })
+                .collect(),
+            media: Vec::new(),
+            thumbnail: item.image.clone().or_else(|| item.banner_image.clone()),
+            categories: item.tags.clone(),
+        }
+    }
+}
+
+impl From<&atom_syndication::Link> for Link {
+    fn from(link: &atom_syndication::Link) -> Self {
+        Self {
+            href: link.href.clone(),
+            rel: link.rel.clone(),
+            mime_type: link.mime_type.clone(),
+            title: link.title.clone(),
+        }
+    }
+}
+
+impl From<&atom_syndication::Person> for Person {
+    fn from(person: &atom_syndication::Person) -> Self {
+        Self {
+            name: person.name.clone(),
+            email: person.email.clone(),
+            uri: person.uri.clone(),
+        }
+    }
+}
+
+impl From<&JsonAuthor> for Person {
+    fn from(author: &JsonAuthor) -> Self {
+        Self {
+            name: author.name.clone().unwrap_or_default(),
+            email: None,
+            uri: author.url.clone(),
+        }
+    }
+}
+
+// rss and atom each have their own extension type of the same shape
+trait Node: Sized {
+    fn attrs(&self) -> &BTreeMap<String, String>;
+    fn children(&self) -> &BTreeMap<String, Vec<Self>>;
+}
+
+impl Node for rss::extension::Extension {
+    fn attrs(&self) -> &BTreeMap<String, String> {
+        &self.attrs
+    }
+    fn children(&self) -> &BTreeMap<String, Vec<Self>> {
+        &self.children
+    }
+}
+
+impl Node for atom_syndication::extension::Extension {
+    fn attrs(&self) -> &BTreeMap<String, String> {
+        &self.attrs
+    }
+    fn children(&self) -> &BTreeMap<String, Vec<Self>> {
+        &self.children
+    }
+}
+
+// media:content and the first media:thumbnail, looking inside media:group as youtube nests them
+fn media<E: Node>(
+    extensions: &BTreeMap<String, BTreeMap<String, Vec<E>>>,
+) -> (Vec<Media>, Option<String>) {
+    let mut found = (Vec::new(), None);
+    if let Some(elements) = extensions.get("media") {
+        for (name, list) in elements {
+            collect_media(name, list, &mut found);
+        }
+    }
+    found
+}
+
+fn collect_media<E: Node>(name: &str, list: &[E], found: &mut (Vec<Media>, Option<String>)) {
+    for ext in list {
+        let attrs = ext.attrs();
+        match name {
+            "content" => {
+                if let Some(url) = attrs.get("url") {
+                    let mime_type = attrs.get("type").cloned();
+                    found.0.push(Media {
+                        url: url.clone(),
+                        medium: attrs
+                            .get("medium")
+                            .cloned()
+                            .or_else(|| mime_type.as_deref().and_then(medium)),
+                        mime_type,
+                        length: attrs.get("fileSize").and_then(|n| n.parse().ok()),
+                    });
+                }
+            }
+            "thumbnail" if found.1.is_none() => found.1 = attrs.get("url").cloned(),
+            "group" => {
+                for (child, list) in ext.children() {
+                    collect_media(child, list, found);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// image/png -> image
+fn medium(mime_type: &str) -> Option<String> {
+    mime_type
+        .split_once('/')
+        .map(|(kind, _)| kind.to_string())
+        .filter(|kind| !kind.is_empty())
+}
+
+fn alternate(href: &str) -> Link {
+    Link {
+        href: href.to_string(),
+        rel: "alternate".to_string(),
+        ..Default::default()
+    }
+}
+
+// the alternate link if there is one, else whatever comes first
+fn alternate_href(links: &[atom_syndication::Link]) -> Option<String> {
+    links
+        .iter()
+        .find(|l| l.rel == "alternate")
+        .or(links.first())
+        .map(|l| l.href.clone())
+}
+
+// rss 1.0 feeds tag with dc:subject rather than category
+fn categories(categories: &[rss::Category], dublin: Option<&DublinCoreExtension>) -> Vec<String> {
+    match dublin {
+        Some(dc) if categories.is_empty() => dc.subjects.clone(),
+        _ => categories.iter().map(|c| c.name.clone()).collect(),
+    }
+}
+
+fn person(name: &str) -> Person {
+    Person {
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+// rss dates should be rfc 2822, dublin core and plenty of real feeds use rfc 3339
+pub fn timestamp(date: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(date)
+        .or_else(|_| DateTime::parse_from_rfc3339(date))
+        .ok()
+        .map(|d| d.timestamp())
+}
+
+// unix seconds back to the dates feeds and pages show
+pub fn rfc2822(secs: i64) -> Option<String> {
+    DateTime::from_timestamp(secs, 0).map(|d| d.to_rfc2822())
+}
+
+pub fn rfc3339(secs: i64) -> Option<String> {
+    DateTime::from_timestamp(secs, 0).map(|d| d.to_rfc3339())
+}
+
+// ids for items that carry neither guid nor link, FNV-1a so they stay stable across builds
+pub fn fallback_id(title: &Option<String>, description: &Option<String>) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in [title, description].into_iter().flatten() {
+        for byte in part.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("fnv:{hash:016x}")
+}
@@ -1,60 +1,196 @@
-use crate::init::ServerBag;
+use crate::init::{self, ClientBag, FeedEdit, FeedEntry, FeedOptions, ServerBag, Settings};
+use crate::model::discover::{self, Candidate};
+use crate::model::doc::Doc;
+use crate::model::jsonfeed::JsonFeed;
+use crate::model::rdf;
+use crate::model::store::{now, ItemQuery, Mark, Record, Source, Store};
 use atom_syndication::Feed;
-use crossbeam::channel::Sender;
+use futures::future::join_all;
 use log::{debug, error, info};
 use quick_cache::{
     sync::{Cache, DefaultLifecycle},
     DefaultHashBuilder, OptionsBuilder, UnitWeighter,
 };
 use reqwest::{
-    header::{HeaderMap, USER_AGENT},
-    Client,
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, USER_AGENT},
+    Client, StatusCode, Url,
 };
 use rss::Channel;
 use std::{
+    collections::HashMap,
+    fmt,
     hash::{DefaultHasher, Hash, Hasher},
     process,
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 use tokio::{
     fs,
     fs::{try_exists, File},
     io::AsyncWriteExt,
+    sync::{oneshot::Sender, Mutex, Notify},
 };
 
 pub enum DataPkt {
-    Error(u16),
+    Error(FeedError),
     Request(String, Sender<DataPkt>),
     ForceRequest(String, Sender<DataPkt>),
-    Channel(Channel),
-    Feed(Feed),
+    Read(ReadTarget, Sender<DataPkt>),
+    Unread(Sender<DataPkt>),
+    Star(i64, bool, Sender<DataPkt>),
+    Starred(Sender<DataPkt>),
+    Search(String, Option<Vec<String>>, Sender<DataPkt>),
+    Timeline(String, Vec<String>, Sender<DataPkt>),
+    Export(Sender<DataPkt>),
+    Subscriptions(Sender<DataPkt>),
+    Edit(FeedEdit, Sender<DataPkt>),
+    Items(ItemQuery, Sender<DataPkt>),
+    Discover(Url, Sender<DataPkt>),
+    Doc(Box<Doc>, Vec<Mark>, Option<i64>),
+    Counts(HashMap<String, usize>),
+    Links(HashMap<String, String>),
+    Entries(Vec<FeedEntry>),
+    Edited(Box<ClientBag>),
+    Rejected(String),
+    Records(Vec<Record>),
+    Candidates(Vec<Candidate>),
+    Done,
 }
 
+// why a request could not be answered, the view shows the details
+#[derive(Debug, Clone)]
+pub enum FeedError {
+    Network(String),
+    Status(u16),
+    Parse(String),
+    UnknownFeed(String),
+    Timeout(String),
+    Disabled,
+    Internal,
+}
+
+impl FeedError {
+    // the http status the error is reported with
+    pub fn status(&self) -> u16 {
+        match self {
+            FeedError::Network(_) | FeedError::Status(_) | FeedError::Parse(_) => 502,
+            FeedError::UnknownFeed(_) => 404,
+            FeedError::Timeout(_) => 504,
+            FeedError::Disabled => 503,
+            FeedError::Internal => 500,
+        }
+    }
+    pub fn reason(&self) -> &'static str {
+        match self {
+            FeedError::Network(_) => "Network error",
+            FeedError::Status(_) => "Bad upstream status",
+            FeedError::Parse(_) => "Unreadable feed",
+            FeedError::UnknownFeed(_) => "Unknown feed",
+            FeedError::Timeout(_) => "Timed out",
+            FeedError::Disabled => "Disabled",
+            FeedError::Internal => "Internal error",
+        }
+    }
+}
+
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedError::Network(e) => write!(f, "Could not fetch the feed: {e}"),
+            FeedError::Status(code) => match StatusCode::from_u16(*code) {
+                Ok(status) => write!(f, "The site answered {status}"),
+                Err(_) => write!(f, "The site answered {code}"),
+            },
+            FeedError::Parse(e) => write!(f, "Not a feed we can read: {e}"),
+            FeedError::UnknownFeed(name) => write!(f, "No feed named {name} is configured"),
+            FeedError::Timeout(e) => write!(f, "{e}"),
+            FeedError::Disabled => write!(f, "The feed is disabled in the config"),
+            FeedError::Internal => write!(f, "Something went wrong on our side, see the log"),
+        }
+    }
+}
+
+pub enum ReadTarget {
+    Item(i64),
+    Feeds(Vec<String>),
+}
+
+// a document together with the state of each of its items and when it was fetched
+type Stored = (Doc, Vec<Mark>, Option<i64>);
+
+// most items rendered for a single feed out of its stored history
+const ITEM_LIMIT: usize = 100;
+// most items returned by a single search
+const SEARCH_LIMIT: usize = 100;
+
 pub struct Fetcher {
-    store: ServerBag,
-    cache: Cache<String, String>,
+    store: RwLock<Arc<ServerBag>>,
+    config: String,
+    overrides: Settings,
+    edits: Mutex<()>,
+    cache: Cache<String, Stored>,
+    client: Client,
+    items: Store,
+    reloaded: Notify,
 }
 
 impl Fetcher {
-    pub fn new(store: ServerBag) -> Self {
+    pub fn new(store: ServerBag, config: String, overrides: Settings) -> Self {
         let lim = {
             if store.cachesz <= u64::MAX as usize {
-                store.cachesz.clone() as u64
+                store.cachesz as u64
             } else {
                 info!("Unable to convert cache size to u64, using 100u64");
                 100u64
             }
         };
-        let cache = Cache::<String, String>::with_options(
+        let cache = Cache::<String, Stored>::with_options(
             OptionsBuilder::new()
                 .weight_capacity(lim)
-                .estimated_items_capacity(store.cachesz.clone())
+                .estimated_items_capacity(store.cachesz)
                 .build()
                 .unwrap(),
             UnitWeighter,
             DefaultHashBuilder::default(),
             DefaultLifecycle::default(),
         );
-        Self { store, cache }
+        let items = Store::new(&store.database);
+        Self {
+            store: RwLock::new(Arc::new(store)),
+            config,
+            overrides,
+            edits: Mutex::new(()),
+            cache,
+            client: Client::new(),
+            items,
+            reloaded: Notify::new(),
+        }
+    }
+    // swaps in a freshly loaded config, cached copies of feeds whose link is unchanged are kept
+    pub fn swap(&self, store: ServerBag) {
+        let old = self.bag();
+        for (name, link) in &old.names {
+            if store.names.get(name) != Some(link) {
+                debug!("Dropping cached copy of {name}");
+                self.cache.remove(name);
+                // validators of the old link mean nothing to the new one
+                if let Err(e) = self.items.forget_validators(name) {
+                    error!("Failed to reset validators of {name}! Error: {e}");
+                }
+            } else if store.options.get(name) != old.options.get(name) {
+                debug!("Options of {name} changed, dropping cached copy");
+                self.cache.remove(name);
+            }
+        }
+        *self.store.write().unwrap() = Arc::new(store);
+        self.reloaded.notify_one();
+    }
+    // resolves once the config has been swapped
+    pub async fn reloaded(&self) {
+        self.reloaded.notified().await
+    }
+    pub fn bag(&self) -> Arc<ServerBag> {
+        Arc::clone(&self.store.read().unwrap())
     }
     pub async fn forceget(&self, data: DataPkt) {
         let (name, tx) = match data {
@@ -67,36 +203,10 @@ impl Fetcher {
 
         info!("Searching for link of {name}");
 
-        match self.store.names.get(&name) {
-            Some(s) => {
-                let client = Client::new();
-                let mut headers = HeaderMap::new();
-                headers.insert(USER_AGENT, self.store.useragent.clone().parse().unwrap());
-
-                match client.get(s).headers(headers).send().await {
-                    Ok(resp) => match resp.text().await {
-                        Ok(txt) => {
-                            if let Ok(channel) = Channel::read_from(&txt.as_bytes()[..]) {
-                                tx.send(DataPkt::Channel(channel)).unwrap();
-                            } else if let Ok(feed) = Feed::read_from(&txt.as_bytes()[..]) {
-                                tx.send(DataPkt::Feed(feed)).unwrap();
-                            }
-                            self.archive(&txt, &name).await;
-                            self.cache.insert(name, txt);
-                        }
-                        Err(_) => {
-                            error!("Failed to parse response from {s}");
-                            tx.send(DataPkt::Error(502)).unwrap();
-                        }
-                    },
-                    Err(_) => {
-                        error!("Failed to fetch from {s}");
-                        tx.send(DataPkt::Error(502)).unwrap();
-                    }
-                }
-            }
-            None => tx.send(DataPkt::Error(502)).unwrap(),
-        } //inner match end
+        match self.download(&name).await {
+            Ok(doc) => reply(tx, doc_pkt(doc)),
+            Err(val) => reply(tx, DataPkt::Error(val)),
+        }
     }
     pub async fn get(&self, data: DataPkt) {
         let (name, tx) = match data {
@@ -109,56 +219,374 @@ impl Fetcher {
 
         info!("Searching for site named {name}");
 
-        match self.cache.get(&name) {
-            Some(s) => {
+        let stored = self.stored(&name);
+        match stored {
+            Some(doc) if self.fresh(&name, &doc) => {
                 debug!("{name} found! returning value");
-                if let Ok(channel) = Channel::read_from(&s.as_bytes()[..]) {
-                    tx.send(DataPkt::Channel(channel)).unwrap();
-                } else if let Ok(feed) = Feed::read_from(&s.as_bytes()[..]) {
-                    tx.send(DataPkt::Feed(feed)).unwrap();
+                reply(tx, doc_pkt(doc));
+            }
+            _ => {
+                debug!("{name} not found or stale! fetching from web!");
+                match (self.download(&name).await, stored) {
+                    (Ok(doc), _) => reply(tx, doc_pkt(doc)),
+                    // a stale copy beats an error page
+                    (Err(_), Some(doc)) => reply(tx, doc_pkt(doc)),
+                    (Err(val), None) => reply(tx, DataPkt::Error(val)),
                 }
             }
-            None => {
-                debug!("{name} not found! fetching from web!");
-                match self.store.names.get(&name) {
-                    Some(s) => {
-                        let client = Client::new();
-                        let mut headers = HeaderMap::new();
-                        headers.insert(USER_AGENT, self.store.useragent.clone().parse().unwrap());
-
-                        match client.get(s).headers(headers).send().await {
-                            Ok(resp) => match resp.text().await {
-                                Ok(txt) => {
-                                    if let Ok(channel) = Channel::read_from(&txt.as_bytes()[..]) {
-                                        tx.send(DataPkt::Channel(channel)).unwrap();
-                                    } else if let Ok(feed) = Feed::read_from(&txt.as_bytes()[..]) {
-                                        tx.send(DataPkt::Feed(feed)).unwrap();
-                                    }
-                                    self.archive(&txt, &name).await;
-                                    self.cache.insert(name, txt);
-                                }
-                                Err(_) => {
-                                    error!("Failed to parse response from {s}");
-                                    tx.send(DataPkt::Error(502)).unwrap();
-                                }
-                            },
-                            Err(_) => {
-                                error!("Failed to fetch from {s}");
-                                tx.send(DataPkt::Error(502)).unwrap();
-                            }
-                        }
+        }
+    }
+    pub async fn mark(&self, data: DataPkt) {
+        let (target, tx) = match data {
+            DataPkt::Read(target, tx) => (target, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        let res = match target {
+            ReadTarget::Item(key) => self.items.mark_item(key),
+            ReadTarget::Feeds(names) => self.items.mark_feeds(&names),
+        };
+        match res {
+            Ok(()) => {
+                // cached marks are stale now
+                self.cache.clear();
+                reply(tx, DataPkt::Done);
+            }
+            Err(e) => {
+                error!("Failed to mark items as read! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn unread(&self, data: DataPkt) {
+        let tx = match data {
+            DataPkt::Unread(tx) => tx,
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        match self.items.unread() {
+            Ok(counts) => reply(tx, DataPkt::Counts(counts)),
+            Err(e) => {
+                error!("Failed to count unread items! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn star(&self, data: DataPkt) {
+        let (key, starred, tx) = match data {
+            DataPkt::Star(key, starred, tx) => (key, starred, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        match self.items.star(key, starred) {
+            Ok(()) => {
+                self.cache.clear();
+                reply(tx, DataPkt::Done);
+            }
+            Err(e) => {
+                error!("Failed to star item {key}! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn starred(&self, data: DataPkt) {
+        let tx = match data {
+            DataPkt::Starred(tx) => tx,
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        match self.items.starred() {
+            Ok((doc, marks)) => reply(tx, DataPkt::Doc(Box::new(doc), marks, None)),
+            Err(e) => {
+                error!("Failed to load starred items! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn search(&self, data: DataPkt) {
+        let (query, feeds, tx) = match data {
+            DataPkt::Search(query, feeds, tx) => (query, feeds, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        match self.items.search(&query, feeds.as_deref(), SEARCH_LIMIT) {
+            Ok((doc, marks)) => reply(tx, DataPkt::Doc(Box::new(doc), marks, None)),
+            Err(e) => {
+                error!("Failed to search for {query}! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn timeline(&self, data: DataPkt) {
+        let (heading, names, tx) = match data {
+            DataPkt::Timeline(heading, names, tx) => (heading, names, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        // feeds that were never fetched have nothing stored to merge yet
+        let missing = names.iter().filter(|name| self.stored(name).is_none());
+        join_all(missing.map(|name| self.download(name))).await;
+
+        match self.items.timeline(&heading, &names, ITEM_LIMIT) {
+            Ok((doc, marks)) => reply(tx, DataPkt::Doc(Box::new(doc), marks, None)),
+            Err(e) => {
+                error!("Failed to build timeline of {heading}! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn refresh(&self, name: &str) {
+        info!("Scheduled refresh of {name}");
+        if let Err(e) = self.download(name).await {
+            error!("Scheduled refresh of {name} failed! {e}");
+        }
+    }
+    pub async fn export(&self, data: DataPkt) {
+        let tx = match data {
+            DataPkt::Export(tx) => tx,
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        reply(tx, DataPkt::Links(self.bag().names.clone()));
+    }
+    pub async fn records(&self, data: DataPkt) {
+        let (query, tx) = match data {
+            DataPkt::Items(query, tx) => (query, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        match self.items.items(&query) {
+            Ok(records) => reply(tx, DataPkt::Records(records)),
+            Err(e) => {
+                error!("Failed to list items! Error: {e}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn discover(&self, data: DataPkt) {
+        let (page, tx) = match data {
+            DataPkt::Discover(page, tx) => (page, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        info!("Looking for feeds of {page}");
+        let useragent = self.bag().useragent.clone();
+        match discover::find(&self.client, &useragent, page.clone()).await {
+            Ok(found) => reply(tx, DataPkt::Candidates(found)),
+            Err(e) => {
+                error!("Failed to look for feeds of {page}! {e}");
+                reply(tx, DataPkt::Error(e));
+            }
+        }
+    }
+    pub async fn subscriptions(&self, data: DataPkt) {
+        let tx = match data {
+            DataPkt::Subscriptions(tx) => tx,
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        match init::read(&self.config) {
+            Ok(config) => reply(tx, DataPkt::Entries(config.feeds)),
+            Err(e) => {
+                error!("Failed to list feeds! Error: {e:#}");
+                reply(tx, DataPkt::Error(FeedError::Internal));
+            }
+        }
+    }
+    pub async fn edit(&self, data: DataPkt) {
+        let (edit, tx) = match data {
+            DataPkt::Edit(edit, tx) => (edit, tx),
+            _ => {
+                error!("Illegal request received! Shutting down");
+                process::exit(-1);
+            }
+        };
+
+        info!("Editing feeds: {edit:?}");
+        // one edit at a time, each one reads what the last one wrote
+        let _guard = self.edits.lock().await;
+        match init::edit(&self.config, &self.overrides, &edit) {
+            Ok((clientbag, serverbag)) => {
+                if let FeedEdit::Rename(name, to) = &edit {
+                    if let Err(e) = self.items.rename(name, to) {
+                        error!("Failed to move history of {name} to {to}! Error: {e}");
                     }
-                    None => tx.send(DataPkt::Error(502)).unwrap(),
                 }
+                self.swap(serverbag);
+                reply(tx, DataPkt::Edited(Box::new(clientbag)));
             }
+            Err(e) => reply(tx, DataPkt::Rejected(format!("{e:#}"))),
+        }
+    }
+    pub fn names(&self) -> Vec<String> {
+        self.bag().names.keys().cloned().collect()
+    }
+    pub fn interval(&self, name: &str) -> Duration {
+        let store = self.bag();
+        let options = self.options(name);
+        // disabled feeds are never polled
+        if options.disabled {
+            return Duration::ZERO;
         }
+        Duration::from_secs(options.refresh.unwrap_or(store.refresh))
+    }
+    fn options(&self, name: &str) -> FeedOptions {
+        self.bag().options.get(name).cloned().unwrap_or_default()
     }
-    async fn archive(&self, data: &str, name: &str) {
-        if self.store.archive_lst.contains(&name.to_string()) {
-            if let Some(mut dir) = self.store.archivedir.clone() {
+    // whether a stored copy is still within the cache ttl of its feed
+    fn fresh(&self, name: &str, (_, _, fetched): &Stored) -> bool {
+        match (self.options(name).cache_ttl, fetched) {
+            (Some(ttl), Some(fetched)) => now() - fetched < ttl as i64,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+    // memory cache first, then whatever history the database holds
+    fn stored(&self, name: &str) -> Option<Stored> {
+        if let Some(doc) = self.cache.get(name) {
+            return Some(doc);
+        }
+        let limit = self.options(name).item_limit.unwrap_or(ITEM_LIMIT);
+        match self.items.load(name, limit) {
+            Ok(Some((doc, marks, fetched))) => {
+                let stored = (doc, marks, Some(fetched));
+                self.cache.insert(name.to_string(), stored.clone());
+                Some(stored)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                error!("Failed to load {name} from the database! Error: {e}");
+                None
+            }
+        }
+    }
+    async fn download(&self, name: &str) -> Result<Stored, FeedError> {
+        let store = self.bag();
+        let link = match store.names.get(name) {
+            Some(s) => s,
+            None => return Err(FeedError::UnknownFeed(name.to_string())),
+        };
+        let options = self.options(name);
+        if options.disabled {
+            debug!("{name} is disabled, not fetching");
+            return Err(FeedError::Disabled);
+        }
+        let mut headers = options.headers;
+        let useragent = options.useragent.as_ref().unwrap_or(&store.useragent);
+        // build() checked both, this only fails if that check is skipped
+        match useragent.parse() {
+            Ok(useragent) => headers.insert(USER_AGENT, useragent),
+            Err(e) => {
+                error!("Bad useragent for {name}! Error: {e}");
+                return Err(FeedError::Internal);
+            }
+        };
+
+        if let Some((etag, last_modified)) = self.items.validators(name) {
+            if let Some(Ok(etag)) = etag.map(|s| s.parse()) {
+                headers.insert(IF_NONE_MATCH, etag);
+            }
+            if let Some(Ok(date)) = last_modified.map(|s| s.parse()) {
+                headers.insert(IF_MODIFIED_SINCE, date);
+            }
+        }
+
+        let mut req = self.client.get(link).headers(headers);
+        if let Some(secs) = options.timeout {
+            req = req.timeout(Duration::from_secs(secs));
+        }
+        let resp = match req.send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("Failed to fetch from {link}! Error: {e}");
+                return Err(request_error(e));
+            }
+        };
+
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Err(e) = self.items.touch(name) {
+                error!("Failed to record fetch of {name}! Error: {e}");
+            }
+            self.cache.remove(name);
+            if let Some(stored) = self.stored(name) {
+                debug!("{name} not modified upstream, serving stored copy");
+                return Ok(stored);
+            }
+        }
+
+        if !resp.status().is_success() {
+            error!("{link} answered {}", resp.status());
+            return Err(FeedError::Status(resp.status().as_u16()));
+        }
+
+        let etag = header_string(resp.headers(), ETAG);
+        let last_modified = header_string(resp.headers(), LAST_MODIFIED);
+        let txt = match resp.text().await {
+            Ok(txt) => txt,
+            Err(e) => {
+                error!("Failed to read response from {link}! Error: {e}");
+                return Err(request_error(e));
+            }
+        };
+        // nothing that fails to parse gets archived, stored or cached
+        let source = match parse(&txt) {
+            Ok(source) => source,
+            Err(e) => {
+                error!("Response from {link} is not a feed! {e}");
+                return Err(FeedError::Parse(e));
+            }
+        };
+
+        self.archive(&txt, &source, name).await;
+        if let Err(e) = self.items.save(name, &source, etag, last_modified) {
+            error!("Failed to store {name}! Error: {e}");
+            return Ok((Doc::from(&source), Vec::new(), None));
+        }
+        self.cache.remove(name);
+        Ok(self
+            .stored(name)
+            .unwrap_or_else(|| (Doc::from(&source), Vec::new(), None)))
+    }
+    async fn archive(&self, data: &str, source: &Source, name: &str) {
+        let store = self.bag();
+        if store.archive_lst.contains(&name.to_string()) {
+            if let Some(mut dir) = store.archivedir.clone() {
                 debug!("Beginning archive process");
                 let mut name = name.to_string();
-                name.push_str(".xml");
+                name.push_str(match source {
+                    Source::Json(_) => ".json",
+                    _ => ".xml",
+                });
                 dir.push(name);
                 //check if filename exits
                 if try_exists(dir.clone()).await.unwrap() {
@@ -189,6 +617,58 @@ impl Fetcher {
     }
 }
 
+// tries every format we read, the error names why the likeliest ones failed
+pub fn parse(txt: &str) -> Result<Source, String> {
+    let rss = match Channel::read_from(txt.as_bytes()) {
+        Ok(channel) => return Ok(Source::Channel(Box::new(channel))),
+        Err(e) => e,
+    };
+    if let Some(channel) = rdf::read(txt) {
+        return Ok(Source::Channel(Box::new(channel)));
+    }
+    let atom = match Feed::read_from(txt.as_bytes()) {
+        Ok(feed) => return Ok(Source::Feed(Box::new(feed))),
+        Err(e) => e,
+    };
+    if let Some(feed) = JsonFeed::read_from(txt) {
+        return Ok(Source::Json(Box::new(feed)));
+    }
+    if txt.trim_start().starts_with('{') {
+        Err("json that is not a json feed".to_string())
+    } else if txt.trim().is_empty() {
+        Err("the response was empty".to_string())
+    } else {
+        Err(format!("as rss: {rss}, as atom: {atom}"))
+    }
+}
+
+// timeouts apart, whatever went wrong talking to the site
+pub fn request_error(e: reqwest::Error) -> FeedError {
+    if e.is_timeout() {
+        FeedError::Timeout("The site took too long to answer".to_string())
+    } else {
+        FeedError::Network(e.to_string())
+    }
+}
+
+fn doc_pkt((doc, marks, fetched): Stored) -> DataPkt {
+    DataPkt::Doc(Box::new(doc), marks, fetched)
+}
+
+// requests may outlive the page that asked for them, a hung up receiver is not an error
+fn reply(tx: Sender<DataPkt>, pkt: DataPkt) {
+    if tx.send(pkt).is_err() {
+        debug!("Requester went away before the reply was ready");
+    }
+}
+
+fn header_string(headers: &HeaderMap, key: reqwest::header::HeaderName) -> Option<String> {
+    headers
+        .get(key)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
 fn calculate_hash<T: Hash>(t: &T) -> u64 {
     let mut s = DefaultHasher::new();
     t.hash(&mut s);
@@ -1,25 +1,152 @@
 use directories::ProjectDirs;
+use eyre::{bail, eyre, Result, WrapErr};
 use log::{debug, error, info};
-use regex::Regex;
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf, process};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Url,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{copy, read_to_string, rename, write},
+    path::PathBuf,
+    process,
+    str::FromStr,
+};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
+
+// a heading named after one of these would have its page hidden by theirs
+const RESERVED: [&str; 8] = [
+    "api", "force", "manage", "search", "starred", "static", "timeline", "unread",
+];
 
 #[derive(Debug, Clone)]
 pub struct ClientBag {
     pub templatedir: PathBuf,
     pub headings: HashMap<String, Vec<String>>,
     pub clients: usize,
+    pub deadline: u64,
+}
+
+// per feed tuning, anything unset falls back to the settings
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FeedOptions {
+    pub refresh: Option<u64>,
+    pub useragent: Option<String>,
+    pub timeout: Option<u64>,
+    pub headers: HeaderMap,
+    pub cache_ttl: Option<u64>,
+    pub item_limit: Option<usize>,
+    pub disabled: bool,
 }
 
 #[derive(Debug)]
 pub struct ServerBag {
     pub names: HashMap<String, String>,
     pub archive_lst: Vec<String>,
+    pub refresh: u64,
+    pub options: HashMap<String, FeedOptions>,
     pub archivedir: Option<PathBuf>,
+    pub database: PathBuf,
     pub useragent: String,
     pub download: usize,
     pub cachesz: usize,
 }
 
+// the config file as written, before defaults are filled in
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub feeds: Vec<FeedEntry>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub useragent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clients: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FeedEntry {
+    pub heading: String,
+    pub name: String,
+    pub link: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub archive: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub useragent: Option<String>,
+    // seconds before a download of this feed is given up on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    // seconds a stored copy is served before a page load fetches it again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
+}
+
+impl Settings {
+    // every field set here wins over the one in under
+    pub fn over(self, under: Settings) -> Settings {
+        Settings {
+            template: self.template.or(under.template),
+            archive: self.archive.or(under.archive),
+            database: self.database.or(under.database),
+            useragent: self.useragent.or(under.useragent),
+            download: self.download.or(under.download),
+            clients: self.clients.or(under.clients),
+            cache_limit: self.cache_limit.or(under.cache_limit),
+            refresh: self.refresh.or(under.refresh),
+            deadline: self.deadline.or(under.deadline),
+        }
+    }
+}
+
+// a change to the feed list made from the web ui
+#[derive(Debug)]
+pub enum FeedEdit {
+    Add(FeedEntry),
+    Rename(String, String),
+    Move(String, String),
+    Archive(String, bool),
+    Delete(String),
+}
+
+fn projdb() -> PathBuf {
+    // returns the default location of the item database
+    let proj_dirs = ProjectDirs::from("com", "Alereader", "Alereader").unwrap();
+    let mut db_file = proj_dirs.data_local_dir().to_path_buf();
+    db_file.push("alereader.db");
+    db_file
+}
+
 fn projfiles() -> (String, String) {
     // returns project dir info, (config file, template folder)
     let proj_dirs = ProjectDirs::from("com", "Alereader", "Alereader").unwrap();
@@ -34,194 +161,556 @@ fn projfiles() -> (String, String) {
     )
 }
 
-pub fn init(file: Option<String>) -> (ClientBag, ServerBag) {
-    //resolve file
-    let file = match file {
+pub fn init(file: Option<String>, overrides: &Settings) -> (ClientBag, ServerBag) {
+    match load(file, overrides) {
+        Ok(bags) => bags,
+        Err(e) => {
+            error!("{e:#}");
+            process::exit(-1);
+        }
+    }
+}
+
+// the config file to read, falling back to the default location
+pub fn resolve(file: Option<String>) -> String {
+    match file {
         Some(s) => s,
         None => {
             info!("No file supplied! Looking for default");
             let (file, _) = projfiles();
             file
         }
-    };
+    }
+}
+
+// overrides win over whatever the file sets
+pub fn load(file: Option<String>, overrides: &Settings) -> Result<(ClientBag, ServerBag)> {
+    let file = resolve(file);
+    build(read(&file)?, overrides)
+}
+
+// the config in file as written, without defaults filled in
+pub fn read(file: &str) -> Result<Config> {
     //open file
-    let contents = match read_to_string(file.clone()) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("Failed to read file {file}! Error: {e}");
-            process::exit(-1);
-        }
-    };
+    let contents = read_to_string(file).wrap_err_with(|| format!("Failed to read file {file}"))?;
 
     debug!("File {file} has been read");
 
-    let lines: Vec<&str> = contents.split("[RssLinks]").collect();
-    if lines.len() == 2 {
-        let (templatedir, archivedir, useragent, download, cachesz, clients) =
-            settings_maker(lines[0].to_string());
-        let (headings, names, archive_lst) = links_maker(lines[1].to_string());
-        (
-            ClientBag {
-                templatedir,
-                headings,
-                clients,
-            },
-            ServerBag {
-                names,
-                archive_lst,
-                archivedir,
-                useragent,
-                download,
-                cachesz,
-            },
-        )
+    parse(&contents).wrap_err_with(|| format!("Failed to parse {file}"))
+}
+
+// the config in file as an editable toml document, comments and layout included. a legacy
+// file has neither worth keeping, it comes back converted to toml
+pub fn document(file: &str) -> Result<DocumentMut> {
+    let contents = read_to_string(file).wrap_err_with(|| format!("Failed to read file {file}"))?;
+    if is_legacy(&contents) {
+        let config = legacy(&contents).wrap_err_with(|| format!("Failed to parse {file}"))?;
+        let contents = toml::to_string(&config).wrap_err("Failed to serialize config")?;
+        return Ok(contents.parse()?);
+    }
+    contents
+        .parse()
+        .wrap_err_with(|| format!("Failed to parse {file}"))
+}
+
+// appends feed as a [[feeds]] table of doc
+pub fn push_feed(doc: &mut DocumentMut, feed: &FeedEntry) -> Result<()> {
+    let table = toml::to_string(feed)
+        .wrap_err("Failed to serialize feed")?
+        .parse::<DocumentMut>()?
+        .as_table()
+        .clone();
+    doc.entry("feeds")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| eyre!("feeds must be written as [[feeds]] tables to be edited"))?
+        .push(table);
+    Ok(())
+}
+
+// the config doc describes, checked the way a load would
+pub fn config(doc: &DocumentMut) -> Result<Config> {
+    toml::from_str(&doc.to_string()).wrap_err("The edited config does not parse")
+}
+
+// writes doc over file, a legacy file is kept next to it as file.legacy first
+pub fn save(file: &str, doc: &DocumentMut) -> Result<()> {
+    if read_to_string(file).is_ok_and(|contents| is_legacy(&contents)) {
+        let backup = format!("{file}.legacy");
+        copy(file, &backup).wrap_err_with(|| format!("Failed to back up {file} to {backup}"))?;
+        info!("{file} is converted to toml, the legacy file is kept as {backup}");
+    }
+    // write next to the original and move it over, so a reload never sees half a file
+    let tmp = format!("{file}.tmp");
+    write(&tmp, doc.to_string()).wrap_err_with(|| format!("Failed to write {tmp}"))?;
+    rename(&tmp, file).wrap_err_with(|| format!("Failed to replace {file}"))?;
+    debug!("File {file} has been written");
+    Ok(())
+}
+
+pub fn parse(contents: &str) -> Result<Config> {
+    if is_legacy(contents) {
+        info!("Config is in the legacy format, consider moving it to toml");
+        legacy(contents)
     } else {
-        error!("File formatting error, [RssLinks] is present either more than 1 times or not present at all");
-        process::exit(-1);
+        Ok(toml::from_str(contents)?)
     }
 }
 
-fn settings_maker(hay: String) -> (PathBuf, Option<PathBuf>, String, usize, usize, usize) {
-    //defining regex patterns
-    let template_rgx = Regex::new(r"\s*template\s*=\s*([^\s]+)\s*").unwrap();
-    let archive_rgx = Regex::new(r"\s*archive\s*=\s*([^\s]+)\s*").unwrap();
-    let ua_rgx = Regex::new(r"\s*useragent\s*=\s*\'([^\']+)\'").unwrap();
-    let download_rgx = Regex::new(r"\s*download\s*=\s*([^\s]+)\s*").unwrap();
-    let cache_rgx = Regex::new(r"\s*cache-limit\s*=\s*([^\s]+)\s*").unwrap();
-    let clients_rgx = Regex::new(r"\s*clients\s*=\s*([^\s]+)\s*").unwrap();
+// files with an [RssLinks] section predate the toml format
+fn is_legacy(contents: &str) -> bool {
+    contents.lines().any(|line| line.trim() == "[RssLinks]")
+}
 
-    //find text
-    let template = match template_rgx.captures(hay.as_str()) {
-        Some(path) => PathBuf::from(path.get(1).unwrap().as_str()),
-        None => {
-            info!("Template location not found, using default");
-            let (_, template_dir) = projfiles();
-            PathBuf::from(template_dir)
+// applies edit to the config in file and writes it back, the file is untouched if the result
+// would not load. only the feed edited changes, comments and layout elsewhere are kept
+pub fn edit(file: &str, overrides: &Settings, edit: &FeedEdit) -> Result<(ClientBag, ServerBag)> {
+    let mut doc = document(file)?;
+    match edit {
+        FeedEdit::Add(feed) => {
+            check(feed)?;
+            push_feed(&mut doc, feed)?;
         }
-    };
-    let archive = match archive_rgx.captures(hay.as_str()) {
-        Some(path) => Some(PathBuf::from(path.get(1).unwrap().as_str())),
-        None => {
-            info!("Archive dir not found, will not be archiving");
-            None
+        FeedEdit::Rename(name, to) => {
+            segment("name", to)?;
+            set(feed_table(&mut doc, name)?, "name", to.as_str());
         }
-    };
-    let useragent = match ua_rgx.captures(hay.as_str()) {
-        Some(path) => path.get(1).unwrap().as_str().to_string(),
-        None => {
-            info!("Useragent not found, using default");
-            String::from(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:126.0) Gecko/20100101 Firefox/126.1",
-            )
+        FeedEdit::Move(name, heading) => {
+            check_heading(heading)?;
+            set(feed_table(&mut doc, name)?, "heading", heading.as_str());
         }
-    };
-    let download = match download_rgx.captures(hay.as_str()) {
-        Some(num) => match num.get(1).unwrap().as_str().parse::<usize>() {
-            Ok(number) => number,
-            Err(_) => {
-                error!("failed to parse download, using defaults");
-                3usize
+        FeedEdit::Archive(name, archive) => {
+            let table = feed_table(&mut doc, name)?;
+            if *archive || table.contains_key("archive") {
+                set(table, "archive", *archive);
             }
-        },
-        None => {
-            info!("No download information found, using default");
-            3usize
         }
-    };
-    let cachesz = match cache_rgx.captures(hay.as_str()) {
-        Some(num) => match num.get(1).unwrap().as_str().parse::<usize>() {
-            Ok(number) => number,
-            Err(_) => {
-                error!("failed to parse download, using defaults");
-                10usize
-            }
-        },
-        None => {
-            info!("No download information found, using default");
-            10usize
+        FeedEdit::Delete(name) => {
+            let feeds = feeds(&mut doc)?;
+            let at = feeds
+                .iter()
+                .position(|table| is_named(table, name))
+                .ok_or_else(|| eyre!("There is no feed named {name}"))?;
+            feeds.remove(at);
         }
-    };
-    let clients = match clients_rgx.captures(hay.as_str()) {
-        Some(num) => match num.get(1).unwrap().as_str().parse::<usize>() {
-            Ok(number) => number,
-            Err(_) => {
-                error!("Failed to parse download, using defaults");
-                3usize
-            }
-        },
-        None => {
-            info!("No download information found, using default");
-            3usize
+    }
+    // build first so duplicate names are caught before anything is written
+    let bags = build(config(&doc)?, overrides)?;
+    if let FeedEdit::Archive(name, true) = edit {
+        if bags.1.archivedir.is_none() {
+            bail!("No archive dir is set, {name} can not be archived");
         }
-    };
-    debug!("Settings parsed!");
+    }
+    save(file, &doc)?;
+    Ok(bags)
+}
+
+fn feeds(doc: &mut DocumentMut) -> Result<&mut ArrayOfTables> {
+    doc.get_mut("feeds")
+        .and_then(Item::as_array_of_tables_mut)
+        .ok_or_else(|| eyre!("feeds must be written as [[feeds]] tables to be edited"))
+}
 
-    //return struct
-    (template, archive, useragent, download, cachesz, clients)
+fn feed_table<'a>(doc: &'a mut DocumentMut, name: &str) -> Result<&'a mut Table> {
+    feeds(doc)?
+        .iter_mut()
+        .find(|table| is_named(table, name))
+        .ok_or_else(|| eyre!("There is no feed named {name}"))
 }
 
-fn links_maker(
-    s: String,
-) -> (
-    HashMap<String, Vec<String>>,
-    HashMap<String, String>,
-    Vec<String>,
-) {
-    //basic data
+fn is_named(table: &Table, name: &str) -> bool {
+    table.get("name").and_then(Item::as_str) == Some(name)
+}
+
+// sets key of table to val, keeping any comment that trails the old value
+fn set(table: &mut Table, key: &str, val: impl Into<Value>) {
+    let mut val = val.into();
+    if let Some(old) = table.get(key).and_then(Item::as_value) {
+        *val.decor_mut() = old.decor().clone();
+    }
+    table.insert(key, Item::Value(val));
+}
+
+// whether feed can be added as it is, its heading and name end up in urls
+pub fn check(feed: &FeedEntry) -> Result<()> {
+    check_heading(&feed.heading)?;
+    segment("name", &feed.name)?;
+    Url::parse(&feed.link).wrap_err_with(|| format!("{} is not a valid link", feed.link))?;
+    Ok(())
+}
+
+fn check_heading(val: &str) -> Result<()> {
+    segment("heading", val)?;
+    if RESERVED.contains(&val) {
+        bail!("The heading {val} is taken by a page of alereader");
+    }
+    Ok(())
+}
+
+// headings and names are path segments in every url
+fn segment(what: &str, val: &str) -> Result<()> {
+    if val.trim().is_empty() {
+        bail!("The {what} can not be empty");
+    }
+    if val.contains('/') {
+        bail!("The {what} {val} can not contain a /");
+    }
+    Ok(())
+}
+
+pub fn build(config: Config, overrides: &Settings) -> Result<(ClientBag, ServerBag)> {
+    let Settings {
+        template,
+        archive,
+        database,
+        useragent,
+        download,
+        clients,
+        cache_limit,
+        refresh,
+        deadline,
+    } = overrides.clone().over(config.settings);
+
+    let templatedir = template.unwrap_or_else(|| {
+        info!("Template location not found, using default");
+        let (_, template_dir) = projfiles();
+        PathBuf::from(template_dir)
+    });
+    if archive.is_none() {
+        info!("Archive dir not found, will not be archiving");
+    }
+    let database = database.unwrap_or_else(|| {
+        info!("Database location not found, using default");
+        projdb()
+    });
+    let useragent = useragent.unwrap_or_else(|| {
+        info!("Useragent not found, using default");
+        String::from(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:126.0) Gecko/20100101 Firefox/126.1",
+        )
+    });
+    HeaderValue::from_str(&useragent).wrap_err("Bad useragent in the settings")?;
+    let download = download.unwrap_or_else(|| {
+        info!("No download information found, using default");
+        3
+    });
+    let cachesz = cache_limit.unwrap_or_else(|| {
+        info!("No cache-limit found, using default");
+        10
+    });
+    let clients = clients.unwrap_or_else(|| {
+        info!("No clients information found, using default");
+        3
+    });
+    let refresh = refresh.unwrap_or_else(|| {
+        info!("No refresh interval found, using default");
+        1800
+    });
+    let deadline = deadline.unwrap_or_else(|| {
+        info!("No heading deadline found, using default");
+        10
+    });
+    debug!("Settings parsed!");
+
     let mut headings = HashMap::new();
     let mut names = HashMap::new();
     let mut archive_lst = Vec::new();
-
-    let rss_pat =
-        Regex::new(r"^([^,]+)\s*,\s*([^,]+)\s*,\s*([^,]+)\s*(?:$|,\s*([A-Za-z]{1,2})$)").unwrap();
-    //find regex
-    let lines: Vec<_> = s.split('\n').filter(|x| !x.is_empty()).collect();
-    for line in lines {
-        debug!("Parsing line {line}");
-        let capts = rss_pat.captures(line).unwrap();
-        let heading = match capts.get(1) {
-            Some(x) => x.as_str(),
-            None => {
-                error!("Error parsing line {line}! Could not parse heading");
-                process::exit(-1);
-            }
-        };
-        let name = match capts.get(2) {
-            Some(x) => x.as_str(),
-            None => {
-                error!("Error parsing line {line}! Could not parse name");
-                process::exit(-1);
-            }
-        };
-        let link = match capts.get(3) {
-            Some(x) => x.as_str(),
-            None => {
-                error!("Error parsing line {line}! Could not parse link");
-                process::exit(-1);
-            }
-        };
-        match capts.get(4) {
-            Some(mat) => {
-                let ma: Vec<char> = mat.as_str().to_string().chars().collect();
-                if ma[0] == 'Y' || ma[0] == 'y' {
-                    archive_lst.push(name.to_string());
-                }
-            }
-            None => {
-                info!("Opts not set for {line}");
-            }
+    let mut options = HashMap::new();
+    for feed in config.feeds {
+        if names.contains_key(&feed.name) {
+            bail!(
+                "Names must be unique, there are two links with the name {}",
+                feed.name
+            );
+        }
+        if feed.archive {
+            archive_lst.push(feed.name.clone());
         }
+        let mut headers = HeaderMap::new();
+        for (key, val) in &feed.headers {
+            let key = HeaderName::from_str(key)
+                .wrap_err_with(|| format!("Bad header name {key} for {}", feed.name))?;
+            let val = HeaderValue::from_str(val)
+                .wrap_err_with(|| format!("Bad value for header {key} of {}", feed.name))?;
+            headers.insert(key, val);
+        }
+        if let Some(useragent) = &feed.useragent {
+            HeaderValue::from_str(useragent)
+                .wrap_err_with(|| format!("Bad useragent for {}", feed.name))?;
+        }
+        if feed.disabled {
+            info!("{} is disabled, it will not be fetched", feed.name);
+        }
+        options.insert(
+            feed.name.clone(),
+            FeedOptions {
+                refresh: feed.refresh,
+                useragent: feed.useragent,
+                timeout: feed.timeout,
+                headers,
+                cache_ttl: feed.cache_ttl,
+                item_limit: feed.item_limit,
+                disabled: feed.disabled,
+            },
+        );
         headings
-            .entry(heading.to_string())
+            .entry(feed.heading)
             .or_insert_with(Vec::new)
-            .push(name.to_string());
-        if names.contains_key(name) {
-            error!("Names must be unique, there are two links with the name {name}");
-            process::exit(-1);
+            .push(feed.name.clone());
+        names.insert(feed.name, feed.link);
+    }
+    debug!("RSS links parsed!");
+
+    if cachesz < names.len() {
+        info!(
+            "cache-limit is smaller than the number of feeds, some page loads will hit the network"
+        );
+    }
+    Ok((
+        ClientBag {
+            templatedir,
+            headings,
+            clients,
+            deadline,
+        },
+        ServerBag {
+            names,
+            archive_lst,
+            refresh,
+            options,
+            archivedir: archive,
+            database,
+            useragent,
+            download,
+            cachesz,
+        },
+    ))
+}
+
+// reads the older `key = value` settings plus `[RssLinks]` csv lines
+fn legacy(contents: &str) -> Result<Config> {
+    let mut config = Config::default();
+    let mut sections = 0;
+    for (num, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line == "[Settings]" {
+            continue;
+        }
+        if line == "[RssLinks]" {
+            sections += 1;
+            if sections > 1 {
+                bail!("line {}: [RssLinks] is present more than once", num + 1);
+            }
+            continue;
+        }
+        let parsed = if sections == 0 {
+            legacy_setting(&mut config.settings, line)
         } else {
-            names.insert(name.to_string(), link.to_string());
+            legacy_link(line).map(|feed| config.feeds.push(feed))
+        };
+        parsed.wrap_err_with(|| format!("line {}: {line}", num + 1))?;
+    }
+    Ok(config)
+}
+
+fn legacy_setting(settings: &mut Settings, line: &str) -> Result<()> {
+    let (key, val) = line
+        .split_once('=')
+        .ok_or_else(|| eyre!("expected `key = value`"))?;
+    let key = key.trim();
+    let val = val.trim().trim_matches('\'');
+    match key {
+        "template" => settings.template = Some(PathBuf::from(val)),
+        "archive" => settings.archive = Some(PathBuf::from(val)),
+        "database" => settings.database = Some(PathBuf::from(val)),
+        "useragent" => settings.useragent = Some(val.to_string()),
+        "download" => settings.download = Some(number(key, val)?),
+        "clients" => settings.clients = Some(number(key, val)?),
+        "cache-limit" => settings.cache_limit = Some(number(key, val)?),
+        "refresh" => settings.refresh = Some(number(key, val)?),
+        "deadline" => settings.deadline = Some(number(key, val)?),
+        _ => bail!("unknown setting `{key}`"),
+    }
+    Ok(())
+}
+
+fn legacy_link(line: &str) -> Result<FeedEntry> {
+    let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+    if !(3..=4).contains(&cols.len()) || cols[..3].iter().any(|col| col.is_empty()) {
+        bail!("expected `heading, name, link` optionally followed by `, opts`");
+    }
+    let mut feed = FeedEntry {
+        heading: cols[0].to_string(),
+        name: cols[1].to_string(),
+        link: cols[2].to_string(),
+        ..Default::default()
+    };
+    // opts are whitespace separated, eg. "y refresh=600 timeout=20"
+    // useragent and headers hold spaces, so they only exist in the toml format
+    for opt in cols.get(3).map_or("", |opts| opts).split_whitespace() {
+        match opt.split_once('=') {
+            Some(("refresh", val)) => feed.refresh = Some(number("refresh", val)?),
+            Some(("timeout", val)) => feed.timeout = Some(number("timeout", val)?),
+            Some(("cache-ttl", val)) => feed.cache_ttl = Some(number("cache-ttl", val)?),
+            Some(("item-limit", val)) => feed.item_limit = Some(number("item-limit", val)?),
+            Some(_) => bail!("unknown option `{opt}`"),
+            None if opt == "disabled" => feed.disabled = true,
+            None if opt.starts_with(['y', 'Y']) => feed.archive = true,
+            None if opt.starts_with(['n', 'N']) => feed.archive = false,
+            None => bail!("unknown option `{opt}`"),
         }
     }
-    debug!("RSS links parsed!");
-    (headings, names, archive_lst)
+    Ok(feed)
+}
+
+fn number<T>(key: &str, val: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    val.parse()
+        .wrap_err_with(|| format!("`{key}` must be a whole number, got `{val}`"))
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // local/config/alereader.conf as it shipped before the toml format
+    const BASELINE: &str = "[Settings]
+template = ./local/share/static
+archive = ./local/share/
+useragent = 'Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/125.0.0.0 Safari/537.36 GLS/100.10.9939.100'
+download = 2
+clients = 2
+cache-limit = 10
+
+[RssLinks]
+Forums, Reddit, https://old.reddit.com/r/all.rss
+Blogs, Hackday, https://hackaday.com/blog/feed/
+News, Guardian International, https://www.theguardian.com/international/rss, y
+";
+
+    fn links(config: &Config) -> Vec<(&str, &str, &str, bool)> {
+        config
+            .feeds
+            .iter()
+            .map(|f| {
+                (
+                    f.heading.as_str(),
+                    f.name.as_str(),
+                    f.link.as_str(),
+                    f.archive,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn baseline_config_loads_unchanged() {
+        let config = parse(BASELINE).unwrap();
+        assert_eq!(
+            config.settings.template,
+            Some(PathBuf::from("./local/share/static"))
+        );
+        assert_eq!(
+            config.settings.archive,
+            Some(PathBuf::from("./local/share/"))
+        );
+        assert!(config
+            .settings
+            .useragent
+            .as_deref()
+            .is_some_and(|ua| ua.starts_with("Mozilla/5.0 (Windows")));
+        assert_eq!(config.settings.download, Some(2));
+        assert_eq!(config.settings.clients, Some(2));
+        assert_eq!(config.settings.cache_limit, Some(10));
+        assert_eq!(
+            links(&config),
+            vec![
+                (
+                    "Forums",
+                    "Reddit",
+                    "https://old.reddit.com/r/all.rss",
+                    false
+                ),
+                ("Blogs", "Hackday", "https://hackaday.com/blog/feed/", false),
+                (
+                    "News",
+                    "Guardian International",
+                    "https://www.theguardian.com/international/rss",
+                    true
+                ),
+            ]
+        );
+
+        let (client, server) = build(config, &Settings::default()).unwrap();
+        assert_eq!(client.headings["News"], vec!["Guardian International"]);
+        assert_eq!(server.archive_lst, vec!["Guardian International"]);
+        assert_eq!(server.download, 2);
+        assert_eq!(server.cachesz, 10);
+    }
+
+    #[test]
+    fn shipped_config_loads() {
+        let config = parse(include_str!("../../local/config/alereader.conf")).unwrap();
+        assert_eq!(config.feeds.len(), 3);
+        assert_eq!(config.settings.refresh, Some(1800));
+        build(config, &Settings::default()).unwrap();
+    }
+
+    #[test]
+    fn malformed_link_names_its_line() {
+        let err = parse("[RssLinks]\nForums, Reddit, https://a.b/\nBlogs, Hackday\n").unwrap_err();
+        assert!(format!("{err:#}").starts_with("line 3: Blogs, Hackday: expected"));
+
+        let err =
+            parse("[RssLinks]\n\n# old\nNews, Bad, https://a.b/, refresh=soon\n").unwrap_err();
+        assert!(format!("{err:#}").contains("line 4: News, Bad, https://a.b/, refresh=soon"));
+    }
+
+    #[test]
+    fn edits_keep_comments() {
+        let file = std::env::temp_dir().join(format!("alereader-edit-{}.toml", process::id()));
+        let file = file.to_str().unwrap();
+        write(
+            file,
+            "# mine\n[[feeds]]\nheading = \"A\"\nname = \"One\" # kept\nlink = \"https://a.b/\"\n",
+        )
+        .unwrap();
+        let overrides = Settings {
+            database: Some(PathBuf::from("unused.db")),
+            ..Default::default()
+        };
+        edit(
+            file,
+            &overrides,
+            &FeedEdit::Rename("One".into(), "Uno".into()),
+        )
+        .unwrap();
+        // no archive dir is set, so the file is left as it was
+        assert!(edit(file, &overrides, &FeedEdit::Archive("Uno".into(), true)).is_err());
+        let written = read_to_string(file).unwrap();
+        std::fs::remove_file(file).unwrap();
+        assert!(written.starts_with("# mine\n"));
+        assert!(written.contains("name = \"Uno\" # kept"));
+        assert!(!written.contains("archive"));
+    }
+
+    #[test]
+    fn reserved_headings_are_refused() {
+        let feed = |heading: &str, name: &str| FeedEntry {
+            heading: heading.to_string(),
+            name: name.to_string(),
+            link: "https://a.b/".to_string(),
+            ..Default::default()
+        };
+        assert!(check(&feed("starred", "One")).is_err());
+        assert!(check(&feed("News", "a/b")).is_err());
+        assert!(check(&feed("Tech-News", "Re-Feed")).is_ok());
+    }
 }
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+pub const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+// what every version url starts with, 1.0 and 1.1 are read alike
+pub const VERSION_PREFIX: &str = "https://jsonfeed.org/version/";
+
+// https://www.jsonfeed.org/version/1.1/, with the singular author of 1.0 kept for reading
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonFeed {
+    pub version: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home_page_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<JsonAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<JsonAuthor>,
+    pub items: Vec<JsonItem>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonItem {
+    // plenty of 1.0 feeds give numbers here
+    #[serde(deserialize_with = "lenient_id")]
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner_image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_published: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<JsonAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<JsonAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<JsonAttachment>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonAuthor {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonAttachment {
+    pub url: String,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_in_bytes: Option<u64>,
+}
+
+impl JsonFeed {
+    // None unless text is a json feed of a version we know
+    pub fn read_from(text: &str) -> Option<Self> {
+        serde_json::from_str::<JsonFeed>(text)
+            .ok()
+            .filter(|feed| feed.version.starts_with(VERSION_PREFIX))
+    }
+    pub fn all_authors(&self) -> Vec<JsonAuthor> {
+        either(&self.authors, &self.author)
+    }
+}
+
+impl JsonItem {
+    pub fn all_authors(&self) -> Vec<JsonAuthor> {
+        either(&self.authors, &self.author)
+    }
+}
+
+// 1.1 authors, or the 1.0 author
+fn either(authors: &[JsonAuthor], author: &Option<JsonAuthor>) -> Vec<JsonAuthor> {
+    match author {
+        Some(author) if authors.is_empty() => vec![author.clone()],
+        _ => authors.to_vec(),
+    }
+}
+
+fn lenient_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => s,
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    })
+}
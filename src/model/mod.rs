@@ -1,2 +1,9 @@
+pub mod discover;
+pub mod doc;
 pub mod fetch;
 pub mod init;
+pub mod jsonfeed;
+pub mod opml;
+pub mod rdf;
+pub mod schedule;
+pub mod store;
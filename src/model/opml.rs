@@ -0,0 +1,199 @@
+use crate::model::init::FeedEntry;
+use eyre::{bail, Result, WrapErr};
+use quick_xml::{
+    events::{BytesDecl, BytesStart, BytesText, Event},
+    Decoder, Reader, Writer,
+};
+use reqwest::Url;
+use std::collections::HashMap;
+
+// heading for feeds that are not inside any folder outline
+const UNFILED: &str = "Imported";
+
+// every feed outline in an opml document, filed under the nearest folder outline around it
+pub fn read(text: &str) -> Result<Vec<FeedEntry>> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+
+    // one entry per open outline, Some(title) when it is a folder
+    let mut folders: Vec<Option<String>> = Vec::new();
+    let mut feeds = Vec::new();
+    let mut opml = false;
+    loop {
+        let event = reader
+            .read_event()
+            .wrap_err_with(|| format!("Malformed opml at byte {}", reader.buffer_position()))?;
+        match event {
+            Event::Start(e) if e.name().as_ref() == b"opml" => opml = true,
+            Event::Start(e) if e.name().as_ref() == b"outline" => {
+                let outline = attributes(&e, reader.decoder())?;
+                match entry(&outline, &folders) {
+                    Some(feed) => {
+                        feeds.push(feed);
+                        folders.push(None);
+                    }
+                    None => folders.push(title(&outline)),
+                }
+            }
+            Event::Empty(e) if e.name().as_ref() == b"outline" => {
+                if let Some(feed) = entry(&attributes(&e, reader.decoder())?, &folders) {
+                    feeds.push(feed);
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"outline" => {
+                folders.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !opml {
+        bail!("Not an opml document, no <opml> element found");
+    }
+    Ok(feeds)
+}
+
+// an opml document with one folder outline per heading
+pub fn write(
+    headings: &HashMap<String, Vec<String>>,
+    names: &HashMap<String, String>,
+) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    let mut sorted: Vec<_> = headings.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    writer
+        .create_element("opml")
+        .with_attribute(("version", "2.0"))
+        .write_inner_content(|w| {
+            w.create_element("head").write_inner_content(|w| {
+                w.create_element("title")
+                    .write_text_content(BytesText::new("Alereader subscriptions"))?;
+                Ok(())
+            })?;
+            w.create_element("body").write_inner_content(|w| {
+                for (heading, list) in &sorted {
+                    w.create_element("outline")
+                        .with_attributes([("text", heading.as_str()), ("title", heading.as_str())])
+                        .write_inner_content(|w| {
+                            for name in list.iter() {
+                                let Some(link) = names.get(name) else {
+                                    continue;
+                                };
+                                w.create_element("outline")
+                                    .with_attributes([
+                                        ("type", "rss"),
+                                        ("text", name.as_str()),
+                                        ("title", name.as_str()),
+                                        ("xmlUrl", link.as_str()),
+                                    ])
+                                    .write_empty()?;
+                            }
+                            Ok(())
+                        })?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+// attribute names are matched case insensitively, exporters disagree on xmlUrl vs xmlurl
+fn attributes(e: &BytesStart, decoder: Decoder) -> Result<HashMap<String, String>> {
+    let mut found = HashMap::new();
+    for attr in e.attributes() {
+        let attr = attr?;
+        let key = String::from_utf8_lossy(attr.key.as_ref()).to_lowercase();
+        found.insert(
+            key,
+            attr.decode_and_unescape_value(decoder)?.trim().to_string(),
+        );
+    }
+    Ok(found)
+}
+
+fn title(outline: &HashMap<String, String>) -> Option<String> {
+    ["title", "text"]
+        .iter()
+        .filter_map(|key| outline.get(*key))
+        .find(|val| !val.is_empty())
+        .cloned()
+}
+
+fn entry(outline: &HashMap<String, String>, folders: &[Option<String>]) -> Option<FeedEntry> {
+    let link = outline.get("xmlurl").filter(|link| !link.is_empty())?;
+    let heading = folders
+        .iter()
+        .rev()
+        .flatten()
+        .next()
+        .cloned()
+        .unwrap_or_else(|| UNFILED.to_string());
+    Some(FeedEntry {
+        heading,
+        // names end up in urls, so an untitled feed is named after its host
+        name: title(outline)
+            .or_else(|| Url::parse(link).ok()?.host_str().map(str::to_string))
+            .unwrap_or_else(|| link.clone()),
+        link: link.clone(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filed(feeds: &[FeedEntry]) -> Vec<(&str, &str)> {
+        feeds
+            .iter()
+            .map(|f| (f.heading.as_str(), f.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn nested_folders_file_under_the_nearest() {
+        let text = r#"<?xml version="1.0"?>
+<opml version="2.0"><head><title>subs</title></head><body>
+  <outline text="Tech">
+    <outline title="Rust">
+      <outline text="Lang">
+        <outline text="Blog" type="rss" xmlUrl="https://blog.rust-lang.org/feed.xml"/>
+      </outline>
+      <outline text="Weekly" xmlurl="https://this-week-in-rust.org/rss.xml"></outline>
+    </outline>
+    <outline text="Top" xmlUrl="https://example.com/top"/>
+  </outline>
+  <outline xmlUrl="https://example.org/loose"/>
+</body></opml>"#;
+        let feeds = read(text).unwrap();
+        assert_eq!(
+            filed(&feeds),
+            vec![
+                ("Lang", "Blog"),
+                ("Rust", "Weekly"),
+                ("Tech", "Top"),
+                (UNFILED, "example.org"),
+            ]
+        );
+        assert_eq!(feeds[0].link, "https://blog.rust-lang.org/feed.xml");
+    }
+
+    #[test]
+    fn round_trips_what_it_writes() {
+        let headings = HashMap::from([("News & Views".to_string(), vec!["Daily".to_string()])]);
+        let names = HashMap::from([("Daily".to_string(), "https://a.b/?x=1&y=2".to_string())]);
+        let feeds = read(&write(&headings, &names).unwrap()).unwrap();
+        assert_eq!(filed(&feeds), vec![("News & Views", "Daily")]);
+        assert_eq!(feeds[0].link, "https://a.b/?x=1&y=2");
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(read("<rss><channel/></rss>").is_err());
+        assert!(read("<opml><body><outline></body></opml>").is_err());
+    }
+}
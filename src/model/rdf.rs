@@ -0,0 +1,217 @@
+use quick_xml::{
+    events::Event,
+    name::{Namespace, ResolveResult},
+    NsReader,
+};
+use rss::{extension::dublincore::DublinCoreExtension, Channel, Image, Item};
+
+const RDF: &[u8] = b"http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const RSS_10: &[u8] = b"http://purl.org/rss/1.0/";
+const RSS_090: &[u8] = b"http://my.netscape.com/rdf/simple/0.9/";
+const DC: &[u8] = b"http://purl.org/dc/elements/1.1/";
+const CONTENT: &[u8] = b"http://purl.org/rss/1.0/modules/content/";
+
+// the part of the document text elements are read into
+enum Scope<'a> {
+    Channel(&'a mut Channel, &'a mut DublinCoreExtension),
+    Item(&'a mut Item, &'a mut DublinCoreExtension),
+    Image(&'a mut Image),
+}
+
+// rss 1.0 and 0.90 documents the rss crate turns down, matched by namespace rather than
+// prefix, read into the same channel it would have produced
+pub fn read(text: &str) -> Option<Channel> {
+    let mut reader = NsReader::from_str(text);
+    reader.config_mut().trim_text(true);
+
+    let mut chan = Channel::default();
+    let mut chan_dc = DublinCoreExtension::default();
+    let mut item: Option<(Item, DublinCoreExtension)> = None;
+    let mut image: Option<Image> = None;
+    let mut in_channel = false;
+    let mut root = false;
+    let mut buf = String::new();
+
+    loop {
+        match reader.read_resolved_event() {
+            Ok((ns, Event::Start(e))) => {
+                let ns = namespace(ns);
+                let local = e.local_name();
+                if !root {
+                    // anything but an rdf root belongs to some other parser
+                    if ns != RDF || local.as_ref() != b"RDF" {
+                        return None;
+                    }
+                    root = true;
+                    continue;
+                }
+                if ns == RSS_10 || ns == RSS_090 {
+                    match local.as_ref() {
+                        b"channel" => in_channel = true,
+                        b"item" => item = Some(Default::default()),
+                        b"image" if !in_channel => image = Some(Default::default()),
+                        _ => {}
+                    }
+                }
+                buf.clear();
+            }
+            Ok((_, Event::Text(t))) => buf.push_str(&t.unescape().ok()?),
+            Ok((_, Event::CData(t))) => buf.push_str(std::str::from_utf8(&t).ok()?),
+            Ok((ns, Event::End(e))) => {
+                let ns = namespace(ns);
+                let local = e.local_name();
+                let text = std::mem::take(&mut buf);
+                let rss = ns == RSS_10 || ns == RSS_090;
+                match local.as_ref() {
+                    b"channel" if rss => in_channel = false,
+                    b"item" if rss => {
+                        if let Some((mut done, dc)) = item.take() {
+                            done.dublin_core_ext = Some(dc);
+                            chan.items.push(done);
+                        }
+                    }
+                    b"image" if rss && image.is_some() => chan.image = image.take(),
+                    name => {
+                        let scope = match (&mut item, &mut image) {
+                            (Some((item, dc)), _) => Scope::Item(item, dc),
+                            (None, Some(image)) => Scope::Image(image),
+                            (None, None) if in_channel => Scope::Channel(&mut chan, &mut chan_dc),
+                            _ => continue,
+                        };
+                        if !text.is_empty() {
+                            assign(scope, ns, name, text);
+                        }
+                    }
+                }
+            }
+            Ok((_, Event::Eof)) => break,
+            Err(_) => return None,
+            _ => {}
+        }
+    }
+
+    if !root || chan.title.is_empty() && chan.items.is_empty() {
+        return None;
+    }
+    if chan.language.is_none() {
+        chan.language = chan_dc.languages.first().cloned();
+    }
+    chan.dublin_core_ext = Some(chan_dc);
+    Some(chan)
+}
+
+fn assign(scope: Scope, ns: &[u8], name: &[u8], text: String) {
+    let rss = ns == RSS_10 || ns == RSS_090;
+    match scope {
+        Scope::Channel(chan, _) if rss => match name {
+            b"title" => chan.title = text,
+            b"link" => chan.link = text,
+            b"description" => chan.description = text,
+            _ => {}
+        },
+        Scope::Item(item, _) if rss => match name {
+            b"title" => item.title = Some(text),
+            b"link" => item.link = Some(text),
+            b"description" => item.description = Some(text),
+            _ => {}
+        },
+        Scope::Item(item, _) if ns == CONTENT && name == b"encoded" => item.content = Some(text),
+        Scope::Channel(_, dc) | Scope::Item(_, dc) if ns == DC => match name {
+            b"date" => dc.dates.push(text),
+            b"creator" => dc.creators.push(text),
+            b"subject" => dc.subjects.push(text),
+            b"language" => dc.languages.push(text),
+            b"rights" => dc.rights.push(text),
+            b"publisher" => dc.publishers.push(text),
+            _ => {}
+        },
+        Scope::Image(image) if rss => match name {
+            b"url" => image.url = text,
+            b"title" => image.title = text,
+            b"link" => image.link = text,
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
+fn namespace<'a>(ns: ResolveResult<'a>) -> &'a [u8] {
+    match ns {
+        ResolveResult::Bound(Namespace(ns)) => ns,
+        _ => b"",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_are_matched_by_namespace() {
+        let text = r#"<?xml version="1.0"?>
+<r:RDF xmlns:r="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+    xmlns:s="http://purl.org/rss/1.0/" xmlns:d="http://purl.org/dc/elements/1.1/"
+    xmlns:c="http://purl.org/rss/1.0/modules/content/">
+  <s:channel r:about="https://example.com/">
+    <s:title>Custom</s:title>
+    <s:link>https://example.com/</s:link>
+    <s:description>Prefixed</s:description>
+    <d:language>en</d:language>
+  </s:channel>
+  <s:image r:about="https://example.com/logo.png">
+    <s:url>https://example.com/logo.png</s:url>
+  </s:image>
+  <s:item r:about="https://example.com/1">
+    <s:title>One</s:title>
+    <s:link>https://example.com/1</s:link>
+    <c:encoded><![CDATA[<p>body</p>]]></c:encoded>
+    <d:date>2024-01-02T03:04:05Z</d:date>
+    <d:creator>Ann</d:creator>
+  </s:item>
+</r:RDF>"#;
+        let chan = read(text).unwrap();
+        assert_eq!(chan.title, "Custom");
+        assert_eq!(chan.link, "https://example.com/");
+        assert_eq!(chan.language.as_deref(), Some("en"));
+        assert_eq!(
+            chan.image.map(|i| i.url).as_deref(),
+            Some("https://example.com/logo.png")
+        );
+        assert_eq!(chan.items.len(), 1);
+        let item = &chan.items[0];
+        assert_eq!(item.title.as_deref(), Some("One"));
+        assert_eq!(item.link.as_deref(), Some("https://example.com/1"));
+        assert_eq!(item.content.as_deref(), Some("<p>body</p>"));
+        let dc = item.dublin_core_ext.as_ref().unwrap();
+        assert_eq!(dc.dates, vec!["2024-01-02T03:04:05Z"]);
+        assert_eq!(dc.creators, vec!["Ann"]);
+    }
+
+    #[test]
+    fn reads_rss_090() {
+        let text = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+    xmlns="http://my.netscape.com/rdf/simple/0.9/">
+  <channel><title>Old</title><link>https://example.com/</link></channel>
+  <item><title>First</title><link>https://example.com/a</link></item>
+  <item><title>Second</title><link>https://example.com/b</link></item>
+</rdf:RDF>"#;
+        let chan = read(text).unwrap();
+        assert_eq!(chan.title, "Old");
+        let titles: Vec<_> = chan
+            .items
+            .iter()
+            .filter_map(|i| i.title.as_deref())
+            .collect();
+        assert_eq!(titles, vec!["First", "Second"]);
+    }
+
+    #[test]
+    fn leaves_other_documents_alone() {
+        assert!(read(r#"<rss version="2.0"><channel><title>x</title></channel></rss>"#).is_none());
+        // the right local name in the wrong namespace is not rdf
+        assert!(
+            read(r#"<RDF xmlns="urn:other"><channel><title>x</title></channel></RDF>"#).is_none()
+        );
+        assert!(read("not xml at all").is_none());
+    }
+}
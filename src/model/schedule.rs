@@ -0,0 +1,49 @@
+use crate::model::fetch::Fetcher;
+use log::debug;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{
+    select,
+    time::{sleep_until, Instant},
+};
+
+// longest the scheduler sleeps before looking at the feed list again
+const MAX_NAP: Duration = Duration::from_secs(60);
+
+pub async fn run(fetcher: Arc<Fetcher>) {
+    let mut due: HashMap<String, Instant> = HashMap::new();
+    loop {
+        let now = Instant::now();
+        let names = fetcher.names();
+        // forget feeds that are no longer configured
+        due.retain(|name, _| names.contains(name));
+
+        for name in names {
+            let interval = fetcher.interval(&name);
+            // a zero interval turns polling off for that feed
+            if interval.is_zero() {
+                continue;
+            }
+            let next = due.entry(name.clone()).or_insert(now);
+            if *next <= now {
+                *next = now + interval;
+                let fetchref = Arc::clone(&fetcher);
+                tokio::spawn(async move {
+                    fetchref.refresh(&name).await;
+                });
+            }
+        }
+
+        let wake = due
+            .values()
+            .min()
+            .copied()
+            .unwrap_or(now + MAX_NAP)
+            .min(now + MAX_NAP);
+        debug!("Scheduler sleeping for {:?}", wake - now);
+        // a reload may bring new feeds, look at them straight away
+        select! {
+            _ = sleep_until(wake) => {}
+            _ = fetcher.reloaded() => debug!("Config reloaded, waking scheduler"),
+        }
+    }
+}
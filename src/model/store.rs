@@ -0,0 +1,741 @@
+use crate::model::doc::{Doc, Post};
+use crate::model::jsonfeed::{JsonFeed, JsonItem};
+use atom_syndication::{Entry, Feed};
+use log::{debug, error};
+use rss::{Channel, Item};
+use rusqlite::{
+    params, params_from_iter,
+    types::{Type, Value},
+    Connection, OptionalExtension,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::Path,
+    process,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// applied in order, the number applied so far is kept in user_version
+const MIGRATIONS: &[&str] = &[
+    "
+CREATE TABLE IF NOT EXISTS feeds (
+    name TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    meta TEXT NOT NULL,
+    etag TEXT,
+    last_modified TEXT,
+    fetched INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS items (
+    key INTEGER PRIMARY KEY AUTOINCREMENT,
+    id TEXT NOT NULL,
+    feed TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    title TEXT,
+    link TEXT,
+    author TEXT,
+    description TEXT,
+    content TEXT,
+    categories TEXT,
+    published INTEGER,
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    raw TEXT NOT NULL,
+    UNIQUE (feed, id)
+);
+CREATE INDEX IF NOT EXISTS items_by_date ON items (feed, published);
+",
+    "ALTER TABLE items ADD COLUMN read INTEGER NOT NULL DEFAULT 0;",
+    "ALTER TABLE items ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;",
+    "
+CREATE VIRTUAL TABLE items_fts USING fts5(
+    title, description, content, author, categories,
+    content = 'items', content_rowid = 'key'
+);
+INSERT INTO items_fts (items_fts) VALUES ('rebuild');
+CREATE TRIGGER items_fts_insert AFTER INSERT ON items BEGIN
+    INSERT INTO items_fts (rowid, title, description, content, author, categories)
+    VALUES (new.key, new.title, new.description, new.content, new.author, new.categories);
+END;
+CREATE TRIGGER items_fts_delete AFTER DELETE ON items BEGIN
+    INSERT INTO items_fts (items_fts, rowid, title, description, content, author, categories)
+    VALUES ('delete', old.key, old.title, old.description, old.content, old.author,
+        old.categories);
+END;
+CREATE TRIGGER items_fts_update
+AFTER UPDATE OF title, description, content, author, categories ON items BEGIN
+    INSERT INTO items_fts (items_fts, rowid, title, description, content, author, categories)
+    VALUES ('delete', old.key, old.title, old.description, old.content, old.author,
+        old.categories);
+    INSERT INTO items_fts (rowid, title, description, content, author, categories)
+    VALUES (new.key, new.title, new.description, new.content, new.author, new.categories);
+END;
+",
+];
+
+// columns read back by mark_row
+const MARK_COLUMNS: &str = "key, read, starred, feed, kind, raw";
+
+// a parsed upstream document, stored as is so nothing is lost to normalizing
+#[derive(Debug, Clone)]
+pub enum Source {
+    Channel(Box<Channel>),
+    Feed(Box<Feed>),
+    Json(Box<JsonFeed>),
+}
+
+impl From<&Source> for Doc {
+    fn from(source: &Source) -> Self {
+        match source {
+            Source::Channel(chan) => Doc::from(chan.as_ref()),
+            Source::Feed(feed) => Doc::from(feed.as_ref()),
+            Source::Json(feed) => Doc::from(feed.as_ref()),
+        }
+    }
+}
+
+// per item state handed to the templates alongside the document
+#[derive(Debug, Clone, Serialize)]
+pub struct Mark {
+    pub key: i64,
+    pub read: bool,
+    pub starred: bool,
+    pub feed: String,
+}
+
+// the searchable columns of a stored item
+struct Row {
+    id: String,
+    title: Option<String>,
+    link: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    content: Option<String>,
+    categories: String,
+    published: Option<i64>,
+    raw: String,
+}
+
+// one stored item with its state
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub post: Post,
+    pub mark: Mark,
+    pub first_seen: i64,
+}
+
+// filters for Store::items, since is compared against first_seen
+#[derive(Debug, Default)]
+pub struct ItemQuery {
+    pub feeds: Option<Vec<String>>,
+    pub since: Option<i64>,
+    pub unread: bool,
+    pub starred: bool,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn new(path: &Path) -> Self {
+        if let Some(dir) = path.parent() {
+            if std::fs::create_dir_all(dir).is_err() {
+                error!("Unable to create database dir {:?}", dir);
+                process::exit(-1);
+            }
+        }
+        let conn = match Connection::open(path) {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to open database {:?}! Error: {e}", path);
+                process::exit(-1);
+            }
+        };
+        if let Err(e) = migrate(&conn) {
+            error!("Failed to set up database {:?}! Error: {e}", path);
+            process::exit(-1);
+        }
+        debug!("Database {:?} opened", path);
+        Self {
+            conn: Mutex::new(conn),
+        }
+    }
+    pub fn save(
+        &self,
+        name: &str,
+        source: &Source,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    ) -> rusqlite::Result<()> {
+        let (kind, meta, rows) = match source {
+            Source::Channel(chan) => {
+                let mut meta = chan.as_ref().clone();
+                meta.items = Vec::new();
+                let rows = chan
+                    .items
+                    .iter()
+                    .map(|item| row(Post::from(item), to_json(item)))
+                    .collect::<Vec<_>>();
+                ("rss", to_json(&meta), rows)
+            }
+            Source::Feed(feed) => {
+                let mut meta = feed.as_ref().clone();
+                meta.entries = Vec::new();
+                let rows = feed
+                    .entries
+                    .iter()
+                    .map(|entry| row(Post::from(entry), to_json(entry)))
+                    .collect::<Vec<_>>();
+                ("atom", to_json(&meta), rows)
+            }
+            Source::Json(feed) => {
+                let mut meta = feed.as_ref().clone();
+                meta.items = Vec::new();
+                let rows = feed
+                    .items
+                    .iter()
+                    .map(|item| row(Post::from(item), to_json(item)))
+                    .collect::<Vec<_>>();
+                ("json", to_json(&meta), rows)
+            }
+        };
+        let now = now();
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO feeds (name, kind, meta, etag, last_modified, fetched)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(name) DO UPDATE SET kind = excluded.kind, meta = excluded.meta,
+                etag = excluded.etag, last_modified = excluded.last_modified,
+                fetched = excluded.fetched",
+            params![name, kind, meta, etag, last_modified, now],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO items (id, feed, kind, title, link, author, description, content,
+                    categories, published, first_seen, last_seen, raw)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?11, ?12)
+                 ON CONFLICT(feed, id) DO UPDATE SET kind = excluded.kind,
+                    title = excluded.title, link = excluded.link, author = excluded.author,
+                    description = excluded.description, content = excluded.content,
+                    categories = excluded.categories, published = excluded.published,
+                    last_seen = excluded.last_seen, raw = excluded.raw",
+            )?;
+            for row in rows {
+                stmt.execute(params![
+                    row.id,
+                    name,
+                    kind,
+                    row.title,
+                    row.link,
+                    row.author,
+                    row.description,
+                    row.content,
+                    row.categories,
+                    row.published,
+                    now,
+                    row.raw
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+    // rebuilds the document for name out of everything stored for it, newest first,
+    // along with when it was last fetched
+    pub fn load(
+        &self,
+        name: &str,
+        limit: usize,
+    ) -> rusqlite::Result<Option<(Doc, Vec<Mark>, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let feed: Option<(String, String, i64)> = conn
+            .query_row(
+                "SELECT kind, meta, fetched FROM feeds WHERE name = ?1",
+                params![name],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .optional()?;
+        let (kind, meta, fetched) = match feed {
+            Some(feed) => feed,
+            None => return Ok(None),
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MARK_COLUMNS} FROM items WHERE feed = ?1
+             ORDER BY published IS NULL, published DESC, first_seen DESC LIMIT ?2"
+        ))?;
+        let rows = stmt
+            .query_map(params![name, limit as i64], mark_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut doc = match kind.as_str() {
+            "atom" => Doc::from(&from_json::<Feed>(&meta)?),
+            "json" => Doc::from(&from_json::<JsonFeed>(&meta)?),
+            _ => Doc::from(&from_json::<Channel>(&meta)?),
+        };
+        let marks = collect(&mut doc, rows);
+        Ok(Some((doc, marks, fetched)))
+    }
+    // records a fetch that found nothing new upstream
+    pub fn touch(&self, name: &str) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE feeds SET fetched = ?2 WHERE name = ?1",
+            params![name, now()],
+        )?;
+        Ok(())
+    }
+    // every starred item as one document
+    pub fn starred(&self) -> rusqlite::Result<(Doc, Vec<Mark>)> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MARK_COLUMNS} FROM items WHERE starred = 1
+             ORDER BY published IS NULL, published DESC, first_seen DESC"
+        ))?;
+        let rows = stmt
+            .query_map([], mark_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(merged("Starred", rows))
+    }
+    // the newest items of several feeds merged into one document
+    pub fn timeline(
+        &self,
+        title: &str,
+        feeds: &[String],
+        limit: usize,
+    ) -> rusqlite::Result<(Doc, Vec<Mark>)> {
+        let conn = self.conn.lock().unwrap();
+        let holders = vec!["?"; feeds.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MARK_COLUMNS} FROM items WHERE feed IN ({holders})
+             ORDER BY published IS NULL, published DESC, first_seen DESC LIMIT {limit}"
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(feeds), mark_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(merged(title, rows))
+    }
+    // full text search over every stored item, optionally limited to some feeds
+    pub fn search(
+        &self,
+        query: &str,
+        feeds: Option<&[String]>,
+        limit: usize,
+    ) -> rusqlite::Result<(Doc, Vec<Mark>)> {
+        let title = format!("Search: {query}");
+        let query = fts_query(query);
+        if query.is_empty() {
+            return Ok(merged(&title, Vec::new()));
+        }
+        let mut args = vec![query];
+        let filter = match feeds {
+            Some(names) => {
+                args.extend(names.iter().cloned());
+                let holders = vec!["?"; names.len()].join(", ");
+                format!("AND items.feed IN ({holders})")
+            }
+            None => String::new(),
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let columns = MARK_COLUMNS
+            .split(", ")
+            .map(|c| format!("items.{c}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {columns} FROM items_fts JOIN items ON items.key = items_fts.rowid
+             WHERE items_fts MATCH ? {filter} ORDER BY items_fts.rank LIMIT {limit}"
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(args), mark_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(merged(&title, rows))
+    }
+    // a page of items matching query, newest first
+    pub fn items(&self, query: &ItemQuery) -> rusqlite::Result<Vec<Record>> {
+        let mut filters = vec!["1 = 1".to_string()];
+        let mut args: Vec<Value> = Vec::new();
+        if let Some(names) = &query.feeds {
+            filters.push(format!("feed IN ({})", vec!["?"; names.len()].join(", ")));
+            args.extend(names.iter().cloned().map(Value::from));
+        }
+        if let Some(since) = query.since {
+            filters.push("first_seen >= ?".to_string());
+            args.push(Value::from(since));
+        }
+        if query.unread {
+            filters.push("read = 0".to_string());
+        }
+        if query.starred {
+            filters.push("starred = 1".to_string());
+        }
+        args.push(Value::from(query.limit as i64));
+        args.push(Value::from(query.offset as i64));
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {MARK_COLUMNS}, first_seen FROM items WHERE {}
+             ORDER BY published IS NULL, published DESC, first_seen DESC, key DESC
+             LIMIT ? OFFSET ?",
+            filters.join(" AND ")
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(args), |r| Ok((mark_row(r)?, r.get(6)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let records = rows
+            .into_iter()
+            .filter_map(|((mark, kind, raw), first_seen)| {
+                Some(Record {
+                    post: post(&kind, &raw)?,
+                    mark,
+                    first_seen,
+                })
+            })
+            .collect();
+        Ok(records)
+    }
+    // nothing deletes a starred item, whatever the archive setting of its feed
+    pub fn star(&self, key: i64, starred: bool) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE items SET starred = ?2 WHERE key = ?1",
+            params![key, starred],
+        )?;
+        Ok(())
+    }
+    pub fn mark_item(&self, key: i64) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE items SET read = 1 WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+    pub fn mark_feeds(&self, names: &[String]) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let holders = vec!["?"; names.len()].join(", ");
+        conn.execute(
+            &format!("UPDATE items SET read = 1 WHERE feed IN ({holders})"),
+            params_from_iter(names),
+        )?;
+        Ok(())
+    }
+    // feed name -> number of unread items, feeds with nothing unread are left out
+    pub fn unread(&self) -> rusqlite::Result<HashMap<String, usize>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT feed, COUNT(*) FROM items WHERE read = 0 GROUP BY feed")?;
+        let counts = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+        Ok(counts)
+    }
+    // moves the history of a feed over to its new name. whatever a deleted feed left under
+    // that name goes first, it would clash with the rows moved in
+    pub fn rename(&self, name: &str, to: &str) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM items WHERE feed = ?1", params![to])?;
+        tx.execute("DELETE FROM feeds WHERE name = ?1", params![to])?;
+        tx.execute(
+            "UPDATE feeds SET name = ?2 WHERE name = ?1",
+            params![name, to],
+        )?;
+        tx.execute(
+            "UPDATE items SET feed = ?2 WHERE feed = ?1",
+            params![name, to],
+        )?;
+        tx.commit()
+    }
+    pub fn forget_validators(&self, name: &str) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE feeds SET etag = NULL, last_modified = NULL WHERE name = ?1",
+            params![name],
+        )?;
+        Ok(())
+    }
+    // (etag, last_modified) from the last successful fetch of name
+    pub fn validators(&self, name: &str) -> Option<(Option<String>, Option<String>)> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT etag, last_modified FROM feeds WHERE name = ?1",
+            params![name],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+        .unwrap_or_else(|e| {
+            error!("Failed to read validators of {name}! Error: {e}");
+            None
+        })
+    }
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (num, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        debug!("Applying database migration {}", num + 1);
+        // a migration that fails halfway must not be counted as applied
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", num + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn mark_row(r: &rusqlite::Row) -> rusqlite::Result<(Mark, String, String)> {
+    Ok((
+        Mark {
+            key: r.get(0)?,
+            read: r.get(1)?,
+            starred: r.get(2)?,
+            feed: r.get(3)?,
+        },
+        r.get(4)?,
+        r.get(5)?,
+    ))
+}
+
+// stored rows of mixed kinds as one document
+fn merged(title: &str, rows: Vec<(Mark, String, String)>) -> (Doc, Vec<Mark>) {
+    let mut doc = Doc {
+        title: title.to_string(),
+        ..Default::default()
+    };
+    let marks = collect(&mut doc, rows);
+    (doc, marks)
+}
+
+// appends the rows that still parse to doc, returning their marks in the same order
+fn collect(doc: &mut Doc, rows: Vec<(Mark, String, String)>) -> Vec<Mark> {
+    let mut marks = Vec::new();
+    for (mark, kind, raw) in rows {
+        if let Some(post) = post(&kind, &raw) {
+            doc.items.push(post);
+            marks.push(mark);
+        }
+    }
+    marks
+}
+
+// the normalized form of a stored item
+fn post(kind: &str, raw: &str) -> Option<Post> {
+    match kind {
+        "atom" => serde_json::from_str::<Entry>(raw)
+            .ok()
+            .map(|e| Post::from(&e)),
+        "json" => serde_json::from_str::<JsonItem>(raw)
+            .ok()
+            .map(|i| Post::from(&i)),
+        _ => serde_json::from_str::<Item>(raw)
+            .ok()
+            .map(|i| Post::from(&i)),
+    }
+}
+
+// every word of the user query becomes a quoted term, so fts5 syntax can't break the query
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn row(post: Post, raw: String) -> Row {
+    Row {
+        author: post.author(),
+        published: post.date(),
+        categories: post.categories.join(" "),
+        id: post.id,
+        title: post.title,
+        link: post.link,
+        description: post.summary,
+        content: post.content,
+        raw,
+    }
+}
+
+fn to_json<T: serde::Serialize>(val: &T) -> String {
+    serde_json::to_string(val).unwrap()
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(val: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(val)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"<rss version="2.0"><channel>
+<title>Test</title><link>https://a.b/</link><description>d</description>
+<item><guid>1</guid><title>Old lighthouse</title><pubDate>Mon, 01 Jan 2024 00:00:00 GMT</pubDate></item>
+<item><guid>2</guid><title>New harbour</title><pubDate>Tue, 02 Jan 2024 00:00:00 GMT</pubDate></item>
+</channel></rss>"#;
+
+    fn store() -> Store {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        Store {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    fn source(rss: &str) -> Source {
+        Source::Channel(Box::new(rss.parse::<Channel>().unwrap()))
+    }
+
+    fn titles(doc: &Doc) -> Vec<&str> {
+        doc.items
+            .iter()
+            .map(|post| post.title.as_deref().unwrap_or_default())
+            .collect()
+    }
+
+    fn found(store: &Store, query: &str) -> Vec<String> {
+        let (doc, _) = store.search(query, None, 10).unwrap();
+        doc.items
+            .iter()
+            .filter_map(|post| post.title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn migrations_run_once() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        migrate(&conn).unwrap();
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn failed_migrations_leave_nothing_behind() {
+        let conn = Connection::open_in_memory().unwrap();
+        // the index of the first migration needs columns this items table lacks
+        conn.execute_batch("CREATE TABLE items (key INTEGER)")
+            .unwrap();
+        assert!(migrate(&conn).is_err());
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |r| r.get(0))
+            .unwrap();
+        let feeds: usize = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'feeds'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!((version, feeds), (0, 0));
+    }
+
+    #[test]
+    fn saved_feeds_load_back() {
+        let store = store();
+        store
+            .save("One", &source(RSS), Some("tag".into()), None)
+            .unwrap();
+        let (loaded, marks, _) = store.load("One", 10).unwrap().unwrap();
+        assert_eq!(loaded.title, "Test");
+        assert_eq!(titles(&loaded), vec!["New harbour", "Old lighthouse"]);
+        assert!(marks.iter().all(|mark| mark.feed == "One" && !mark.read));
+        assert_eq!(store.validators("One"), Some((Some("tag".into()), None)));
+        assert!(store.load("Two", 10).unwrap().is_none());
+
+        // items are kept as the json of what was parsed
+        let raw: String = store
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT raw FROM items WHERE id = '1'", [], |r| r.get(0))
+            .unwrap();
+        let item: Item = serde_json::from_str(&raw).unwrap();
+        assert_eq!(item.title(), Some("Old lighthouse"));
+    }
+
+    #[test]
+    fn saving_again_keeps_marks() {
+        let store = store();
+        store.save("One", &source(RSS), None, None).unwrap();
+        let (_, marks, _) = store.load("One", 10).unwrap().unwrap();
+        store.mark_item(marks[0].key).unwrap();
+        store.star(marks[1].key, true).unwrap();
+
+        store
+            .save(
+                "One",
+                &source(&RSS.replace("New harbour", "New pier")),
+                None,
+                None,
+            )
+            .unwrap();
+        let (loaded, again, _) = store.load("One", 10).unwrap().unwrap();
+        assert_eq!(titles(&loaded), vec!["New pier", "Old lighthouse"]);
+        assert_eq!(again[0].key, marks[0].key);
+        assert!(again[0].read && !again[0].starred);
+        assert!(again[1].starred && !again[1].read);
+        assert_eq!(store.starred().unwrap().0.items.len(), 1);
+    }
+
+    #[test]
+    fn search_follows_the_items() {
+        let store = store();
+        store.save("One", &source(RSS), None, None).unwrap();
+        assert_eq!(found(&store, "harbour"), vec!["New harbour"]);
+
+        store
+            .save(
+                "One",
+                &source(&RSS.replace("New harbour", "New pier")),
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(found(&store, "harbour").is_empty());
+        assert_eq!(found(&store, "pier"), vec!["New pier"]);
+
+        store
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM items WHERE id = '2'", [])
+            .unwrap();
+        assert!(found(&store, "pier").is_empty());
+        assert_eq!(found(&store, "lighthouse"), vec!["Old lighthouse"]);
+    }
+
+    #[test]
+    fn renaming_replaces_leftover_history() {
+        let store = store();
+        store.save("One", &source(RSS), None, None).unwrap();
+        // Two was deleted from the config, its rows are still here
+        store
+            .save(
+                "Two",
+                &source(&RSS.replace("New harbour", "New pier")),
+                None,
+                None,
+            )
+            .unwrap();
+        store.rename("One", "Two").unwrap();
+        assert!(store.load("One", 10).unwrap().is_none());
+        let (loaded, _, _) = store.load("Two", 10).unwrap().unwrap();
+        assert_eq!(titles(&loaded), vec!["New harbour", "Old lighthouse"]);
+        assert!(found(&store, "pier").is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    model::{
+        fetch::Fetcher,
+        init::{self, Settings},
+    },
+    view::client::Controller,
+};
+use actix_web::web::Data;
+use log::{debug, error, info};
+use std::{
+    fs::metadata,
+    future::pending,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    select,
+    signal::unix::{signal, SignalKind},
+    time::interval,
+};
+
+// how often the config file is checked for changes
+const POLL: Duration = Duration::from_secs(5);
+
+// reloads the config on SIGHUP or when the file changes on disk
+pub async fn watch(
+    file: String,
+    overrides: Settings,
+    fetcher: Arc<Fetcher>,
+    controller: Data<Controller>,
+) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => Some(s),
+        Err(e) => {
+            error!("Failed to listen for SIGHUP, only watching {file}! Error: {e}");
+            None
+        }
+    };
+    let mut modified = mtime(&file);
+    let mut poll = interval(POLL);
+
+    loop {
+        select! {
+            _ = async {
+                match hangup.as_mut() {
+                    Some(s) => s.recv().await,
+                    None => pending().await,
+                }
+            } => info!("SIGHUP received, reloading {file}"),
+            _ = poll.tick() => {
+                if mtime(&file) == modified {
+                    continue;
+                }
+                info!("{file} changed on disk, reloading");
+            }
+        }
+        modified = mtime(&file);
+
+        let (clientbag, serverbag) = match init::load(Some(file.clone()), &overrides) {
+            Ok(bags) => bags,
+            Err(e) => {
+                error!("Reload failed, keeping the running config! Error: {e:#}");
+                continue;
+            }
+        };
+
+        // these are baked into the running server
+        let (oldclient, oldserver) = (controller.bag(), fetcher.bag());
+        if clientbag.templatedir != oldclient.templatedir
+            || clientbag.clients != oldclient.clients
+            || serverbag.database != oldserver.database
+            || serverbag.download != oldserver.download
+            || serverbag.cachesz != oldserver.cachesz
+        {
+            info!("template, database, download, clients and cache-limit only change on restart");
+        }
+
+        debug!("Swapping in {} feeds", serverbag.names.len());
+        fetcher.swap(serverbag);
+        controller.swap(clientbag);
+        info!("Config reloaded from {file}");
+    }
+}
+
+fn mtime(file: &str) -> Option<SystemTime> {
+    metadata(file).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,264 @@
+use crate::model::{
+    doc::{rfc3339, Media},
+    fetch::DataPkt,
+    store::{ItemQuery, Record},
+};
+use crate::view::client::Controller;
+use actix_web::{
+    get,
+    web::{Data, Path, Query},
+    HttpResponse,
+};
+use chrono::DateTime;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::oneshot;
+
+const PAGE_DEFAULT: usize = 50;
+const PAGE_MAX: usize = 500;
+
+#[derive(Deserialize)]
+pub struct ItemsQuery {
+    feed: Option<String>,
+    heading: Option<String>,
+    since: Option<String>,
+    #[serde(default)]
+    unread: bool,
+    #[serde(default)]
+    starred: bool,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Serialize)]
+struct ApiHeading {
+    name: String,
+    feeds: Vec<String>,
+    unread: usize,
+}
+
+#[derive(Serialize)]
+struct ApiFeed {
+    name: String,
+    heading: String,
+    link: String,
+    unread: usize,
+}
+
+// the same shape for every item whether it came from rss or atom
+#[derive(Serialize)]
+struct ApiItem {
+    id: i64,
+    guid: String,
+    feed: String,
+    title: Option<String>,
+    link: Option<String>,
+    author: Option<String>,
+    summary: Option<String>,
+    content: Option<String>,
+    categories: Vec<String>,
+    published: Option<String>,
+    seen: String,
+    read: bool,
+    starred: bool,
+    enclosures: Vec<Media>,
+}
+
+#[derive(Serialize)]
+struct ApiPage {
+    items: Vec<ApiItem>,
+    offset: usize,
+    limit: usize,
+    next: Option<usize>,
+}
+
+impl From<Record> for ApiItem {
+    fn from(record: Record) -> Self {
+        let post = record.post;
+        Self {
+            id: record.mark.key,
+            guid: post.id.clone(),
+            feed: record.mark.feed,
+            title: post.title.clone(),
+            link: post.link.clone(),
+            author: post.author(),
+            summary: post.summary.clone(),
+            content: post.content.clone(),
+            categories: post.categories.clone(),
+            published: post.date().and_then(rfc3339),
+            seen: rfc3339(record.first_seen).unwrap_or_default(),
+            read: record.mark.read,
+            starred: record.mark.starred,
+            enclosures: post.enclosures,
+        }
+    }
+}
+
+#[get("/api/v1/headings")]
+pub async fn apiheadings(data: Data<Controller>) -> HttpResponse {
+    info!("Request for /api/v1/headings");
+    let counts = data.unread_counts().await;
+    let mut headings: Vec<ApiHeading> = data
+        .bag()
+        .headings
+        .iter()
+        .map(|(name, feeds)| ApiHeading {
+            name: name.clone(),
+            feeds: feeds.clone(),
+            unread: feeds.iter().filter_map(|f| counts.get(f)).sum(),
+        })
+        .collect();
+    headings.sort_by(|a, b| a.name.cmp(&b.name));
+    HttpResponse::Ok().json(headings)
+}
+
+#[get("/api/v1/feeds")]
+pub async fn apifeeds(data: Data<Controller>) -> HttpResponse {
+    info!("Request for /api/v1/feeds");
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx.send(DataPkt::Export(req_tx)).unwrap();
+    let names = match req_rx.await {
+        Ok(DataPkt::Links(names)) => names,
+        _ => return failure(500, "could not list feeds"),
+    };
+    let counts = data.unread_counts().await;
+    let mut feeds: Vec<ApiFeed> = data
+        .bag()
+        .headings
+        .iter()
+        .flat_map(|(heading, list)| list.iter().map(move |name| (heading, name)))
+        .filter_map(|(heading, name)| {
+            Some(ApiFeed {
+                name: name.clone(),
+                heading: heading.clone(),
+                link: names.get(name)?.clone(),
+                unread: counts.get(name).copied().unwrap_or(0),
+            })
+        })
+        .collect();
+    feeds.sort_by(|a, b| (&a.heading, &a.name).cmp(&(&b.heading, &b.name)));
+    HttpResponse::Ok().json(feeds)
+}
+
+#[get("/api/v1/items")]
+pub async fn apiitems(data: Data<Controller>, query: Query<ItemsQuery>) -> HttpResponse {
+    info!("Request for /api/v1/items");
+    let query = query.into_inner();
+    let feeds = match (&query.feed, &query.heading) {
+        (Some(_), Some(_)) => Err((400, "give either feed or heading, not both".to_string())),
+        (Some(feed), None) => feed_list(&data, feed),
+        (None, Some(heading)) => heading_feeds(&data, heading),
+        (None, None) => Ok(Some(
+            data.bag().headings.values().flatten().cloned().collect(),
+        )),
+    };
+    itempage(&data, feeds, query).await
+}
+
+#[get("/api/v1/headings/{heading}/items")]
+pub async fn apiheadingitems(
+    data: Data<Controller>,
+    heading: Path<String>,
+    query: Query<ItemsQuery>,
+) -> HttpResponse {
+    info!("Request for /api/v1/headings/{}/items", heading);
+    itempage(&data, heading_feeds(&data, &heading), query.into_inner()).await
+}
+
+#[get("/api/v1/feeds/{name}/items")]
+pub async fn apifeeditems(
+    data: Data<Controller>,
+    name: Path<String>,
+    query: Query<ItemsQuery>,
+) -> HttpResponse {
+    info!("Request for /api/v1/feeds/{}/items", name);
+    itempage(&data, feed_list(&data, &name), query.into_inner()).await
+}
+
+// fetches one more item than asked for to tell whether another page follows
+async fn itempage(
+    data: &Controller,
+    feeds: Result<Option<Vec<String>>, Failure>,
+    query: ItemsQuery,
+) -> HttpResponse {
+    let feeds = match feeds {
+        Ok(feeds) => feeds,
+        Err((status, message)) => return failure(status, &message),
+    };
+    let since = match query.since.as_deref().map(timestamp) {
+        Some(None) => return failure(400, "since must be unix seconds or an RFC 3339 date"),
+        Some(since) => since,
+        None => None,
+    };
+    let limit = query.limit.unwrap_or(PAGE_DEFAULT).clamp(1, PAGE_MAX);
+    // sqlite takes the offset as a signed 64 bit number
+    if i64::try_from(query.offset).is_err() {
+        return failure(400, "offset is too large");
+    }
+    let itemquery = ItemQuery {
+        feeds,
+        since,
+        unread: query.unread,
+        starred: query.starred,
+        limit: limit + 1,
+        offset: query.offset,
+    };
+
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx
+        .send(DataPkt::Items(itemquery, req_tx))
+        .unwrap();
+    match req_rx.await {
+        Ok(DataPkt::Records(mut records)) => {
+            let next = (records.len() > limit)
+                .then(|| query.offset.checked_add(limit))
+                .flatten();
+            records.truncate(limit);
+            HttpResponse::Ok().json(ApiPage {
+                items: records.into_iter().map(ApiItem::from).collect(),
+                offset: query.offset,
+                limit,
+                next,
+            })
+        }
+        _ => failure(500, "could not list items"),
+    }
+}
+
+fn heading_feeds(data: &Controller, heading: &str) -> Result<Option<Vec<String>>, Failure> {
+    match data.bag().headings.get(heading) {
+        Some(list) => Ok(Some(list.clone())),
+        None => Err((404, format!("no heading named {heading}"))),
+    }
+}
+
+fn feed_list(data: &Controller, name: &str) -> Result<Option<Vec<String>>, Failure> {
+    match data
+        .bag()
+        .headings
+        .values()
+        .any(|list| list.iter().any(|f| f == name))
+    {
+        true => Ok(Some(vec![name.to_string()])),
+        false => Err((404, format!("no feed named {name}"))),
+    }
+}
+
+// a status and message, turned into a response by failure
+type Failure = (u16, String);
+
+fn failure(status: u16, message: &str) -> HttpResponse {
+    let status = actix_web::http::StatusCode::from_u16(status).unwrap();
+    HttpResponse::build(status).json(json!({ "error": message }))
+}
+
+// accepts unix seconds or an RFC 3339 date
+fn timestamp(value: &str) -> Option<i64> {
+    value.parse::<i64>().ok().or_else(|| {
+        DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|d| d.timestamp())
+    })
+}
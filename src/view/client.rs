@@ -1,109 +1,467 @@
-use crate::model::{fetch::DataPkt, init::ClientBag};
+use crate::model::{
+    discover,
+    fetch::{DataPkt, FeedError, ReadTarget},
+    init::{ClientBag, FeedEdit, FeedEntry},
+    opml,
+};
 use crate::view::compose::View;
 use actix_web::{
-    get,
-    web::{Data, Path},
+    delete, get,
+    http::StatusCode,
+    post,
+    web::{Data, Json, Path, Query},
     HttpResponse,
 };
-use crossbeam::channel::{unbounded, Receiver, Sender};
-use log::info;
-use std::collections::HashMap;
+use futures::future::join_all;
+use log::{error, info};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc::UnboundedSender, oneshot},
+    time::{timeout_at, Instant},
+};
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: String,
+    heading: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedForm {
+    heading: String,
+    name: String,
+    link: String,
+}
+
+#[derive(Deserialize)]
+pub struct DiscoverQuery {
+    url: String,
+}
+
+#[derive(Deserialize)]
+pub struct RenameForm {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct MoveForm {
+    heading: String,
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveForm {
+    archive: bool,
+}
 
 pub struct Controller {
     pub view: View,
-    store: ClientBag,
-    pub view_tx: Sender<DataPkt>,
+    store: RwLock<Arc<ClientBag>>,
+    pub view_tx: UnboundedSender<DataPkt>,
 }
 
 impl Controller {
-    pub fn new(store: ClientBag, view_tx: Sender<DataPkt>) -> Self {
+    pub fn new(store: ClientBag, view_tx: UnboundedSender<DataPkt>) -> Self {
         let view = View::new(store.templatedir.clone());
 
         Self {
             view,
-            store,
+            store: RwLock::new(Arc::new(store)),
             view_tx,
         }
     }
-    pub fn headings_ref(&self) -> &HashMap<String, Vec<String>> {
-        return &self.store.headings;
+    pub fn bag(&self) -> Arc<ClientBag> {
+        Arc::clone(&self.store.read().unwrap())
+    }
+    // swaps in a freshly loaded config, later requests see the new headings
+    pub fn swap(&self, store: ClientBag) {
+        *self.store.write().unwrap() = Arc::new(store);
+    }
+    pub async fn unread_counts(&self) -> HashMap<String, usize> {
+        let (req_tx, req_rx) = oneshot::channel();
+        self.view_tx.send(DataPkt::Unread(req_tx)).unwrap();
+        match req_rx.await {
+            Ok(DataPkt::Counts(counts)) => counts,
+            _ => HashMap::new(),
+        }
+    }
+    async fn star(&self, key: i64, starred: bool) -> Option<u16> {
+        let (req_tx, req_rx) = oneshot::channel();
+        self.view_tx
+            .send(DataPkt::Star(key, starred, req_tx))
+            .unwrap();
+        match req_rx.await {
+            Ok(DataPkt::Done) => None,
+            Ok(DataPkt::Error(e)) => Some(e.status()),
+            _ => Some(500),
+        }
+    }
+    // sends edit to the fetcher, which persists it, and swaps in the headings that result
+    async fn edit(&self, edit: FeedEdit) -> HttpResponse {
+        let (req_tx, req_rx) = oneshot::channel();
+        self.view_tx.send(DataPkt::Edit(edit, req_tx)).unwrap();
+        match req_rx.await {
+            Ok(DataPkt::Edited(store)) => {
+                self.swap(*store);
+                HttpResponse::Ok().finish()
+            }
+            Ok(DataPkt::Rejected(reason)) => HttpResponse::BadRequest().body(reason),
+            _ => HttpResponse::InternalServerError().body(self.view.serveerror(500).await),
+        }
+    }
+    async fn mark_read(&self, target: ReadTarget) -> Option<u16> {
+        let (req_tx, req_rx) = oneshot::channel();
+        self.view_tx.send(DataPkt::Read(target, req_tx)).unwrap();
+        match req_rx.await {
+            Ok(DataPkt::Done) => None,
+            Ok(DataPkt::Error(e)) => Some(e.status()),
+            _ => Some(500),
+        }
     }
 }
 
 #[get("/")]
 pub async fn gethome(data: Data<Controller>) -> HttpResponse {
     info!("Request for /");
-    return HttpResponse::Ok().body(data.view.servehome(&data.headings_ref()).await);
+    let unread = data.unread_counts().await;
+    HttpResponse::Ok().body(data.view.servehome(&data.bag().headings, &unread).await)
 }
 
-#[get("/force/{headings}/{name}/")]
-pub async fn getforcefeed(data: Data<Controller>, name: Path<(String, String)>) -> HttpResponse {
-    info!("Request for /force/{}/{}", name.0.clone(), name.1.clone());
-    let (req_tx, req_rx): (Sender<DataPkt>, Receiver<DataPkt>) = unbounded();
+#[get("/export.opml")]
+pub async fn getopml(data: Data<Controller>) -> HttpResponse {
+    info!("Request for /export.opml");
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx.send(DataPkt::Export(req_tx)).unwrap();
+    match req_rx.await {
+        Ok(DataPkt::Links(names)) => match opml::write(&data.bag().headings, &names) {
+            Ok(body) => HttpResponse::Ok().content_type("text/x-opml").body(body),
+            Err(e) => {
+                error!("Failed to build opml! Error: {e:#}");
+                HttpResponse::InternalServerError().body(data.view.serveerror(500).await)
+            }
+        },
+        _ => HttpResponse::InternalServerError().body(data.view.serveerror(500).await),
+    }
+}
+
+#[get("/manage/")]
+pub async fn getmanage(data: Data<Controller>) -> HttpResponse {
+    info!("Request for /manage/");
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx.send(DataPkt::Subscriptions(req_tx)).unwrap();
+    let ret = match req_rx.await {
+        Ok(DataPkt::Entries(feeds)) => data.view.servemanage(feeds).await,
+        Ok(DataPkt::Error(e)) => data.view.serveerror(e.status()).await,
+        _ => data.view.serveerror(500).await,
+    };
+    HttpResponse::Ok().body(ret)
+}
+
+// feeds found for a site, as json for the add form to pick from
+#[get("/manage/discover/")]
+pub async fn discoverfeeds(data: Data<Controller>, query: Query<DiscoverQuery>) -> HttpResponse {
+    info!("Request for /manage/discover/ with {}", query.url);
+    let page = match discover::page_url(&query.url) {
+        Ok(page) => page,
+        Err(reason) => return HttpResponse::BadRequest().body(reason),
+    };
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx.send(DataPkt::Discover(page, req_tx)).unwrap();
+    match req_rx.await {
+        Ok(DataPkt::Candidates(found)) => HttpResponse::Ok().json(found),
+        Ok(DataPkt::Error(e)) => match StatusCode::from_u16(e.status()) {
+            Ok(status) => HttpResponse::build(status).body(e.to_string()),
+            Err(_) => HttpResponse::InternalServerError().body(e.to_string()),
+        },
+        _ => HttpResponse::InternalServerError().body("Failed to look for feeds"),
+    }
+}
+
+#[post("/manage/add/")]
+pub async fn addfeed(data: Data<Controller>, form: Json<FeedForm>) -> HttpResponse {
+    info!("Request for /manage/add/ with {}", form.name);
+    let FeedForm {
+        heading,
+        name,
+        link,
+    } = form.into_inner();
+    data.edit(FeedEdit::Add(FeedEntry {
+        heading: heading.trim().to_string(),
+        name: name.trim().to_string(),
+        link: link.trim().to_string(),
+        ..Default::default()
+    }))
+    .await
+}
+
+#[post("/manage/{name}/rename/")]
+pub async fn renamefeed(
+    data: Data<Controller>,
+    name: Path<String>,
+    form: Json<RenameForm>,
+) -> HttpResponse {
+    info!("Request for /manage/{}/rename/", name.clone());
+    let to = form.name.trim().to_string();
+    data.edit(FeedEdit::Rename(name.into_inner(), to)).await
+}
+
+#[post("/manage/{name}/move/")]
+pub async fn movefeed(
+    data: Data<Controller>,
+    name: Path<String>,
+    form: Json<MoveForm>,
+) -> HttpResponse {
+    info!("Request for /manage/{}/move/", name.clone());
+    let heading = form.heading.trim().to_string();
+    data.edit(FeedEdit::Move(name.into_inner(), heading)).await
+}
+
+#[post("/manage/{name}/archive/")]
+pub async fn archivefeed(
+    data: Data<Controller>,
+    name: Path<String>,
+    form: Json<ArchiveForm>,
+) -> HttpResponse {
+    info!("Request for /manage/{}/archive/", name.clone());
+    data.edit(FeedEdit::Archive(name.into_inner(), form.archive))
+        .await
+}
+
+#[delete("/manage/{name}/")]
+pub async fn deletefeed(data: Data<Controller>, name: Path<String>) -> HttpResponse {
+    info!("Request to delete /manage/{}/", name.clone());
+    data.edit(FeedEdit::Delete(name.into_inner())).await
+}
+
+#[get("/unread/")]
+pub async fn getunread(data: Data<Controller>) -> HttpResponse {
+    info!("Request for /unread/");
+    HttpResponse::Ok().json(data.unread_counts().await)
+}
+
+#[post("/read/item/{key}/")]
+pub async fn readitem(data: Data<Controller>, key: Path<i64>) -> HttpResponse {
+    info!("Request for /read/item/{}/", key);
+    match data.mark_read(ReadTarget::Item(key.into_inner())).await {
+        None => HttpResponse::Ok().finish(),
+        Some(val) => HttpResponse::InternalServerError().body(data.view.serveerror(val).await),
+    }
+}
+
+#[post("/read/{heading}/{name}/")]
+pub async fn readfeed(data: Data<Controller>, name: Path<(String, String)>) -> HttpResponse {
+    info!("Request for /read/{}/{}/", name.0.clone(), name.1.clone());
+    match data
+        .mark_read(ReadTarget::Feeds(vec![name.1.clone()]))
+        .await
+    {
+        None => HttpResponse::Ok().finish(),
+        Some(val) => HttpResponse::InternalServerError().body(data.view.serveerror(val).await),
+    }
+}
+
+#[post("/read/{heading}/")]
+pub async fn readheading(data: Data<Controller>, name: Path<String>) -> HttpResponse {
+    info!("Request for /read/{}/", name.clone());
+    match data.bag().headings.get(&name.to_string()) {
+        Some(list) => match data.mark_read(ReadTarget::Feeds(list.clone())).await {
+            None => HttpResponse::Ok().finish(),
+            Some(val) => HttpResponse::InternalServerError().body(data.view.serveerror(val).await),
+        },
+        None => HttpResponse::NotFound().body(data.view.serveerror(404).await),
+    }
+}
+
+#[post("/star/{key}/")]
+pub async fn staritem(data: Data<Controller>, key: Path<i64>) -> HttpResponse {
+    info!("Request for /star/{}/", key);
+    match data.star(key.into_inner(), true).await {
+        None => HttpResponse::Ok().finish(),
+        Some(val) => HttpResponse::InternalServerError().body(data.view.serveerror(val).await),
+    }
+}
+
+#[post("/unstar/{key}/")]
+pub async fn unstaritem(data: Data<Controller>, key: Path<i64>) -> HttpResponse {
+    info!("Request for /unstar/{}/", key);
+    match data.star(key.into_inner(), false).await {
+        None => HttpResponse::Ok().finish(),
+        Some(val) => HttpResponse::InternalServerError().body(data.view.serveerror(val).await),
+    }
+}
+
+#[get("/starred/")]
+pub async fn getstarred(data: Data<Controller>) -> HttpResponse {
+    info!("Request for /starred/");
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx.send(DataPkt::Starred(req_tx)).unwrap();
+    if let Ok(result) = req_rx.await {
+        let ret = match result {
+            DataPkt::Error(e) => data.view.serveerror(e.status()).await,
+            DataPkt::Doc(doc, marks, _) => data.view.servefeed(*doc, marks, None, "", "").await,
+            _ => data.view.serveerror(404).await,
+        };
+        HttpResponse::Ok().body(ret)
+    } else {
+        HttpResponse::Ok().body(data.view.serveerror(500).await)
+    }
+}
+
+#[get("/search/")]
+pub async fn getsearch(data: Data<Controller>, query: Query<SearchQuery>) -> HttpResponse {
+    info!("Request for /search/ with {}", query.q);
+    let heading = query.heading.as_ref().filter(|h| !h.is_empty());
+    let name = query.name.as_ref().filter(|n| !n.is_empty());
+    let feeds = match (heading, name) {
+        (_, Some(name)) => Some(vec![name.clone()]),
+        (Some(heading), None) => match data.bag().headings.get(heading) {
+            Some(list) => Some(list.clone()),
+            None => return HttpResponse::NotFound().body(data.view.serveerror(404).await),
+        },
+        (None, None) => None,
+    };
+
+    let (req_tx, req_rx) = oneshot::channel();
     data.view_tx
-        .send(DataPkt::Request(name.1.clone(), req_tx))
+        .send(DataPkt::Search(query.q.clone(), feeds, req_tx))
+        .unwrap();
+    if let Ok(result) = req_rx.await {
+        let ret = match result {
+            DataPkt::Error(e) => data.view.serveerror(e.status()).await,
+            DataPkt::Doc(doc, marks, _) => data.view.servefeed(*doc, marks, None, "", "").await,
+            _ => data.view.serveerror(404).await,
+        };
+        HttpResponse::Ok().body(ret)
+    } else {
+        HttpResponse::Ok().body(data.view.serveerror(500).await)
+    }
+}
+
+#[get("/timeline/{heading}/")]
+pub async fn gettimeline(data: Data<Controller>, name: Path<String>) -> HttpResponse {
+    info!("Request for /timeline/{}/", name.clone());
+    let list = match data.bag().headings.get(&name.to_string()) {
+        Some(list) => list.clone(),
+        None => return HttpResponse::NotFound().body(data.view.serveerror(404).await),
+    };
+
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx
+        .send(DataPkt::Timeline(name.to_string(), list, req_tx))
         .unwrap();
-    if let Ok(result) = req_rx.recv() {
+    if let Ok(result) = req_rx.await {
         let ret = match result {
-            DataPkt::Error(val) => data.view.serveerror(val).await,
-            DataPkt::Channel(chan) => data.view.servefeed_rss(chan).await,
-            DataPkt::Feed(feed) => data.view.servefeed_atom(feed).await,
+            DataPkt::Error(e) => data.view.serveerror(e.status()).await,
+            DataPkt::Doc(doc, marks, _) => data.view.servefeed(*doc, marks, None, "", "").await,
             _ => data.view.serveerror(404).await,
         };
-        return HttpResponse::Ok().body(ret);
+        HttpResponse::Ok().body(ret)
     } else {
-        return HttpResponse::Ok().body(data.view.serveerror(500).await);
+        HttpResponse::Ok().body(data.view.serveerror(500).await)
     }
 }
 
+#[get("/force/{headings}/{name}/")]
+pub async fn getforcefeed(data: Data<Controller>, name: Path<(String, String)>) -> HttpResponse {
+    info!("Request for /force/{}/{}", name.0.clone(), name.1.clone());
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx
+        .send(DataPkt::ForceRequest(name.1.clone(), req_tx))
+        .unwrap();
+    feedview(&data, &name.0, &name.1, req_rx).await
+}
+
+#[get("/force/{heading}/")]
+pub async fn getforcefull(data: Data<Controller>, name: Path<String>) -> HttpResponse {
+    info!("Request for /force/{}/", name.clone());
+    headingview(&data, &name, true).await
+}
+
 #[get("/{heading}/{name}/")]
 pub async fn getfeed(data: Data<Controller>, name: Path<(String, String)>) -> HttpResponse {
     info!("Request for /{}/{}", name.0.clone(), name.1.clone());
-    let (req_tx, req_rx): (Sender<DataPkt>, Receiver<DataPkt>) = unbounded();
+    let (req_tx, req_rx) = oneshot::channel();
     data.view_tx
         .send(DataPkt::Request(name.1.clone(), req_tx))
         .unwrap();
-    if let Ok(result) = req_rx.recv() {
+    feedview(&data, &name.0, &name.1, req_rx).await
+}
+
+#[get("/{heading}/")]
+pub async fn getfull(data: Data<Controller>, name: Path<String>) -> HttpResponse {
+    info!("Request for /{}/", name.clone());
+    headingview(&data, &name, false).await
+}
+
+async fn feedview(
+    data: &Controller,
+    heading: &str,
+    feed: &str,
+    req_rx: oneshot::Receiver<DataPkt>,
+) -> HttpResponse {
+    if let Ok(result) = req_rx.await {
         let ret = match result {
-            DataPkt::Error(val) => data.view.serveerror(val).await,
-            DataPkt::Channel(chan) => data.view.servefeed_rss(chan).await,
-            DataPkt::Feed(feed) => data.view.servefeed_atom(feed).await,
+            DataPkt::Error(e) => data.view.servefeederror(&e, feed).await,
+            DataPkt::Doc(doc, marks, fetched) => {
+                data.view
+                    .servefeed(*doc, marks, fetched, heading, feed)
+                    .await
+            }
             _ => data.view.serveerror(404).await,
         };
-        return HttpResponse::Ok().body(ret);
+        HttpResponse::Ok().body(ret)
     } else {
-        return HttpResponse::Ok().body(data.view.serveerror(500).await);
+        HttpResponse::Ok().body(data.view.serveerror(500).await)
     }
 }
 
-#[get("/{heading}/")]
-pub async fn getfull(data: Data<Controller>, name: Path<String>) -> HttpResponse {
-    info!("Request for /{}/", name.clone());
-    let headings = data.headings_ref();
-    if let Some(list) = headings.get(&name.to_string()) {
-        let mut composite = String::new();
-        let (req_tx, req_rx): (Sender<DataPkt>, Receiver<DataPkt>) = unbounded();
-
-        for name in list {
-            data.view_tx
-                .send(DataPkt::Request(name.to_string(), req_tx.clone()))
-                .unwrap();
-
-            if let Ok(result) = req_rx.recv() {
-                let ret = match result {
-                    DataPkt::Error(val) => data.view.serveerror(val).await,
-                    DataPkt::Channel(chan) => data.view.servefeed_rss(chan).await,
-                    DataPkt::Feed(feed) => data.view.servefeed_atom(feed).await,
-                    _ => data.view.serveerror(404).await,
-                };
-                composite.push_str(&ret);
+async fn headingview(data: &Controller, heading: &str, force: bool) -> HttpResponse {
+    let bag = data.bag();
+    if let Some(list) = bag.headings.get(heading) {
+        let deadline = Instant::now() + Duration::from_secs(bag.deadline);
+
+        // ask for every feed up front, then collect whatever is ready in time
+        let pending = list.iter().map(|feed| {
+            let (req_tx, req_rx) = oneshot::channel();
+            let req = if force {
+                DataPkt::ForceRequest(feed.to_string(), req_tx)
             } else {
-                composite.push_str(&data.view.serveerror(500).await)
-            }
+                DataPkt::Request(feed.to_string(), req_tx)
+            };
+            data.view_tx.send(req).unwrap();
+            async move { (feed, timeout_at(deadline, req_rx).await) }
+        });
+        let results = join_all(pending).await;
+
+        let mut composite = String::new();
+        for (feed, result) in results {
+            let ret = match result {
+                Ok(Ok(DataPkt::Error(e))) => data.view.servefeederror(&e, feed).await,
+                Ok(Ok(DataPkt::Doc(doc, marks, fetched))) => {
+                    data.view
+                        .servefeed(*doc, marks, fetched, heading, feed)
+                        .await
+                }
+                Ok(_) => data.view.servefeederror(&FeedError::Internal, feed).await,
+                Err(_) => {
+                    info!("{feed} missed the deadline of /{heading}/");
+                    let e = FeedError::Timeout(format!(
+                        "No answer within the {}s the page waits for",
+                        bag.deadline
+                    ));
+                    data.view.servefeederror(&e, feed).await
+                }
+            };
+            composite.push_str(&ret);
         }
 
-        return HttpResponse::Ok().body(composite);
+        HttpResponse::Ok().body(composite)
     } else {
-        return HttpResponse::Ok().body(data.view.serveerror(404).await);
+        HttpResponse::Ok().body(data.view.serveerror(404).await)
     }
 }
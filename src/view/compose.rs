@@ -1,118 +1,111 @@
-use atom_syndication::Feed;
+use crate::model::{
+    doc::{rfc2822, Doc},
+    fetch::FeedError,
+    init::FeedEntry,
+    store::Mark,
+};
+use eyre::{eyre, Report};
 use log::{debug, error};
-use minijinja::{context, Environment, Value};
-use rss::Channel;
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf, process};
+use minijinja::{context, AutoEscape, Environment, Value};
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    process,
+};
 
 pub struct View {
     env: Environment<'static>,
 }
 
+// every template the views render, as (name, file)
+const TEMPLATES: [(&str, &str); 4] = [
+    ("home", "home.html"),
+    ("feed", "feed.html"),
+    ("manage", "manage.html"),
+    ("error", "error.html"),
+];
+
 impl View {
     pub fn new(templatedir: PathBuf) -> Self {
-        //making env
-        let mut env = Environment::new();
-        let mut homefile = templatedir.clone();
-        homefile.push("home.html");
-        let mut feedfile = templatedir.clone();
-        feedfile.push("feed.html");
-        let mut channelfile = templatedir.clone();
-        channelfile.push("channel.html");
-        let mut errorfile = templatedir.clone();
-        errorfile.push("error.html");
-
-        let homecontents = match read_to_string(homefile.clone()) {
-            Ok(s) => s,
-            Err(_) => {
-                error!("Failed to read homefile!");
-                process::exit(-1);
-            }
-        };
-        let feedcontents = match read_to_string(feedfile.clone()) {
-            Ok(s) => s,
-            Err(_) => {
-                error!("Failed to read feedfile!");
-                process::exit(-1);
-            }
-        };
-        let errorcontents = match read_to_string(errorfile.clone()) {
-            Ok(s) => s,
-            Err(_) => {
-                error!("Failed to read errorfile!");
-                process::exit(-1);
-            }
-        };
-        let channelcontents = match read_to_string(channelfile.clone()) {
-            Ok(s) => s,
-            Err(_) => {
-                error!("Failed to read channelfile!");
-                process::exit(-1);
-            }
-        };
-
-        match env.add_template_owned(String::from("home"), homecontents) {
-            Ok(()) => debug!("home.html has been parsed!"),
-            Err(_) => {
-                error!("Failed to add homefile to collection!");
-                process::exit(-1);
-            }
-        }
-        match env.add_template_owned(String::from("feed"), feedcontents) {
-            Ok(()) => debug!("feed.html has been parsed!"),
-            Err(_) => {
-                error!("Failed to add feedfile to collection!");
-                process::exit(-1);
-            }
-        }
-        match env.add_template_owned(String::from("channel"), channelcontents) {
-            Ok(()) => debug!("channel.html has been parsed!"),
-            Err(_) => {
-                error!("Failed to add channelfile to collection!");
-                process::exit(-1);
-            }
-        }
-        match env.add_template_owned(String::from("error"), errorcontents) {
-            Ok(()) => debug!("error.html has been parsed!"),
-            Err(_) => {
-                error!("Failed to add errorfile to collection!");
-                process::exit(-1);
+        let (env, problems) = templates(&templatedir);
+        if !problems.is_empty() {
+            for e in problems {
+                error!("{e:#}");
             }
+            process::exit(-1);
         }
 
         Self { env }
     }
-    pub async fn servefeed_rss(&self, data: Channel) -> String {
-        let tmp = self.env.get_template("channel").unwrap();
-        let ctx = Value::from_serialize(data);
-        match tmp.render(ctx) {
-            Ok(s) => return s,
-            Err(_) => return self.serveerror(500).await,
-        }
+    // every problem with the templates in templatedir, empty if View::new would succeed
+    pub fn check(templatedir: &Path) -> Vec<Report> {
+        templates(templatedir).1
     }
-    pub async fn servefeed_atom(&self, data: Feed) -> String {
+    pub async fn servefeed(
+        &self,
+        data: Doc,
+        marks: Vec<Mark>,
+        fetched: Option<i64>,
+        heading: &str,
+        feed: &str,
+    ) -> String {
         let tmp = self.env.get_template("feed").unwrap();
-        let ctx = Value::from_serialize(data);
+        let ctx = context!(
+            marks => marks,
+            refreshed => fetched.and_then(rfc2822),
+            heading => heading,
+            feed => feed,
+            ..Value::from_serialize(data)
+        );
         match tmp.render(ctx) {
-            Ok(s) => return s,
-            Err(_) => return self.serveerror(500).await,
+            Ok(s) => s,
+            Err(_) => self.serveerror(500).await,
         }
     }
-    pub async fn servehome(&self, url_data: &HashMap<String, Vec<String>>) -> String {
+    pub async fn servehome(
+        &self,
+        url_data: &HashMap<String, Vec<String>>,
+        unread: &HashMap<String, usize>,
+    ) -> String {
         let home = self.env.get_template("home").unwrap();
         let mut dat = Vec::new();
         for i in url_data.keys() {
-            dat.push(context!(heading => i, names => url_data.get(i).unwrap()))
+            let names = url_data.get(i).unwrap();
+            let total: usize = names.iter().filter_map(|n| unread.get(n)).sum();
+            dat.push(context!(heading => i, names => names, unread => unread, total => total))
         }
         match home.render(context!(headings => dat)) {
-            Ok(s) => return s,
-            Err(_) => return self.serveerror(500).await,
+            Ok(s) => s,
+            Err(_) => self.serveerror(500).await,
         }
     }
 
+    pub async fn servemanage(&self, feeds: Vec<FeedEntry>) -> String {
+        let tmp = self.env.get_template("manage").unwrap();
+        match tmp.render(context!(feeds => feeds)) {
+            Ok(s) => s,
+            Err(_) => self.serveerror(500).await,
+        }
+    }
+
+    pub async fn servefeederror(&self, e: &FeedError, feed: &str) -> String {
+        let tmp = self.env.get_template("error").unwrap();
+        let ctx = context!(
+            error => e.status(),
+            reason => e.reason(),
+            detail => e.to_string(),
+            feed => feed
+        );
+        match tmp.render(ctx) {
+            Ok(s) => s,
+            Err(_) => self.serveerror(500).await,
+        }
+    }
     pub async fn serveerror(&self, val: u16) -> String {
         let tmp = self.env.get_template("error").unwrap();
         match tmp.render(context!(error => val)) {
-            Ok(s) => return s,
+            Ok(s) => s,
             Err(_) => {
                 error!("Unable to render error template! Shutting down");
                 process::exit(-1);
@@ -120,3 +113,28 @@ impl View {
         }
     }
 }
+
+fn templates(templatedir: &Path) -> (Environment<'static>, Vec<Report>) {
+    //making env
+    let mut env = Environment::new();
+    // names are registered without .html, so escaping has to be asked for. feed html is
+    // marked safe in the templates that show it
+    env.set_auto_escape_callback(|_| AutoEscape::Html);
+    env.add_filter("datetime", |secs: i64| rfc2822(secs).unwrap_or_default());
+    let mut problems = Vec::new();
+    for (name, file) in TEMPLATES {
+        let path = templatedir.join(file);
+        let contents = match read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                problems.push(eyre!("Failed to read {}: {e}", path.display()));
+                continue;
+            }
+        };
+        match env.add_template_owned(name, contents) {
+            Ok(()) => debug!("{file} has been parsed!"),
+            Err(e) => problems.push(eyre!("Failed to parse {}: {e}", path.display())),
+        }
+    }
+    (env, problems)
+}
@@ -0,0 +1,303 @@
+use crate::model::{
+    doc::{rfc2822, rfc3339, Doc, Post},
+    fetch::DataPkt,
+    jsonfeed::{self, JsonAttachment, JsonAuthor, JsonFeed, JsonItem},
+    store::now,
+};
+use crate::view::client::Controller;
+use actix_web::{
+    get,
+    web::{Data, Path},
+    HttpRequest, HttpResponse,
+};
+use atom_syndication::{Content, Entry, Feed, FixedDateTime, Generator, Text};
+use chrono::DateTime;
+use log::info;
+use reqwest::Url;
+use rss::{extension::atom::AtomExtension, Channel, Guid, Item};
+use tokio::sync::oneshot;
+
+const GENERATOR: &str = "Alereader";
+
+#[get("/{heading}/feed.xml")]
+pub async fn getheadingrss(
+    data: Data<Controller>,
+    heading: Path<String>,
+    req: HttpRequest,
+) -> HttpResponse {
+    info!("Request for /{}/feed.xml", heading);
+    match merged(&data, &heading).await {
+        Ok(doc) => {
+            let chan = channel(
+                &doc,
+                &heading,
+                page(&req, &[&heading, ""]),
+                page(&req, &[&heading, "feed.xml"]),
+            );
+            HttpResponse::Ok()
+                .content_type("application/rss+xml")
+                .body(chan.to_string())
+        }
+        Err(resp) => resp,
+    }
+}
+
+#[get("/{heading}/atom.xml")]
+pub async fn getheadingatom(
+    data: Data<Controller>,
+    heading: Path<String>,
+    req: HttpRequest,
+) -> HttpResponse {
+    info!("Request for /{}/atom.xml", heading);
+    match merged(&data, &heading).await {
+        Ok(doc) => {
+            let feed = feed(
+                &doc,
+                &heading,
+                page(&req, &[&heading, ""]),
+                page(&req, &[&heading, "atom.xml"]),
+            );
+            HttpResponse::Ok()
+                .content_type("application/atom+xml")
+                .body(feed.to_string())
+        }
+        Err(resp) => resp,
+    }
+}
+
+#[get("/{heading}/feed.json")]
+pub async fn getheadingjson(
+    data: Data<Controller>,
+    heading: Path<String>,
+    req: HttpRequest,
+) -> HttpResponse {
+    info!("Request for /{}/feed.json", heading);
+    match merged(&data, &heading).await {
+        Ok(doc) => {
+            let feed = jsonfeed(
+                &doc,
+                &heading,
+                page(&req, &[&heading, ""]),
+                page(&req, &[&heading, "feed.json"]),
+            );
+            HttpResponse::Ok()
+                .content_type("application/feed+json")
+                .json(feed)
+        }
+        Err(resp) => resp,
+    }
+}
+
+// the stored items of every feed under heading, newest first
+async fn merged(data: &Controller, heading: &str) -> Result<Doc, HttpResponse> {
+    let list = match data.bag().headings.get(heading) {
+        Some(list) => list.clone(),
+        None => return Err(HttpResponse::NotFound().body(data.view.serveerror(404).await)),
+    };
+    let (req_tx, req_rx) = oneshot::channel();
+    data.view_tx
+        .send(DataPkt::Timeline(heading.to_string(), list, req_tx))
+        .unwrap();
+    match req_rx.await {
+        Ok(DataPkt::Doc(doc, _, _)) => Ok(*doc),
+        _ => Err(HttpResponse::InternalServerError().body(data.view.serveerror(500).await)),
+    }
+}
+
+fn channel(doc: &Doc, heading: &str, home: String, this: String) -> Channel {
+    Channel {
+        title: heading.to_string(),
+        link: home,
+        description: format!("Everything under {heading}"),
+        last_build_date: newest(doc).and_then(rfc2822),
+        generator: Some(GENERATOR.to_string()),
+        atom_ext: Some(AtomExtension {
+            links: vec![link(this, "self", "application/rss+xml")],
+        }),
+        items: doc.items.iter().map(item).collect(),
+        ..Default::default()
+    }
+}
+
+fn item(post: &Post) -> Item {
+    Item {
+        title: post.title.clone(),
+        link: post.link.clone(),
+        description: post.summary.clone(),
+        author: post.author(),
+        categories: post
+            .categories
+            .iter()
+            .map(|c| rss::Category {
+                name: c.clone(),
+                domain: None,
+            })
+            .collect(),
+        comments: post.comments.clone(),
+        // rss allows a single enclosure per item
+        enclosure: post.enclosures.first().map(|e| rss::Enclosure {
+            url: e.url.clone(),
+            length: e.length.unwrap_or(0).to_string(),
+            mime_type: e.mime_type.clone().unwrap_or_default(),
+        }),
+        guid: Some(Guid {
+            value: post.id.clone(),
+            permalink: false,
+        }),
+        pub_date: post.date().and_then(rfc2822),
+        content: post.content.clone(),
+        ..Default::default()
+    }
+}
+
+fn feed(doc: &Doc, heading: &str, home: String, this: String) -> Feed {
+    Feed {
+        title: Text::plain(heading),
+        id: this.clone(),
+        updated: fixed(newest(doc).unwrap_or_else(now)),
+        links: vec![
+            link(this, "self", "application/atom+xml"),
+            link(home, "alternate", "text/html"),
+        ],
+        generator: Some(Generator {
+            value: GENERATOR.to_string(),
+            ..Default::default()
+        }),
+        entries: doc.items.iter().map(entry).collect(),
+        ..Default::default()
+    }
+}
+
+fn entry(post: &Post) -> Entry {
+    let mut links = Vec::new();
+    if let Some(href) = &post.link {
+        links.push(atom_syndication::Link {
+            href: href.clone(),
+            rel: "alternate".to_string(),
+            ..Default::default()
+        });
+    }
+    links.extend(post.enclosures.iter().map(|e| atom_syndication::Link {
+        href: e.url.clone(),
+        rel: "enclosure".to_string(),
+        mime_type: e.mime_type.clone(),
+        length: e.length.map(|n| n.to_string()),
+        ..Default::default()
+    }));
+    Entry {
+        title: Text::plain(post.title.clone().unwrap_or_default()),
+        id: post.id.clone(),
+        updated: fixed(post.updated.or(post.published).unwrap_or_else(now)),
+        published: post.published.map(fixed),
+        authors: post
+            .authors
+            .iter()
+            .map(|a| atom_syndication::Person {
+                name: a.name.clone(),
+                email: a.email.clone(),
+                uri: a.uri.clone(),
+            })
+            .collect(),
+        categories: post
+            .categories
+            .iter()
+            .map(|c| atom_syndication::Category {
+                term: c.clone(),
+                ..Default::default()
+            })
+            .collect(),
+        links,
+        summary: post.summary.clone().map(Text::html),
+        content: post.content.clone().map(|c| Content {
+            value: Some(c),
+            content_type: Some("html".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn jsonfeed(doc: &Doc, heading: &str, home: String, this: String) -> JsonFeed {
+    JsonFeed {
+        version: jsonfeed::VERSION.to_string(),
+        title: heading.to_string(),
+        home_page_url: Some(home),
+        feed_url: Some(this),
+        items: doc.items.iter().map(jsonitem).collect(),
+        ..Default::default()
+    }
+}
+
+fn jsonitem(post: &Post) -> JsonItem {
+    // content_html is required unless content_text is given, the summary stands in for it
+    let (content, summary) = match &post.content {
+        Some(content) => (content.clone(), post.summary.clone()),
+        None => (post.summary.clone().unwrap_or_default(), None),
+    };
+    JsonItem {
+        id: post.id.clone(),
+        url: post.link.clone(),
+        title: post.title.clone(),
+        content_html: Some(content),
+        summary,
+        image: post.thumbnail.clone(),
+        date_published: post.published.and_then(rfc3339),
+        date_modified: post.updated.and_then(rfc3339),
+        authors: post
+            .authors
+            .iter()
+            .map(|a| JsonAuthor {
+                name: Some(a.name.clone()),
+                url: a.uri.clone(),
+                ..Default::default()
+            })
+            .collect(),
+        tags: post.categories.clone(),
+        attachments: post
+            .enclosures
+            .iter()
+            .map(|e| JsonAttachment {
+                url: e.url.clone(),
+                mime_type: e
+                    .mime_type
+                    .clone()
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                size_in_bytes: e.length,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn newest(doc: &Doc) -> Option<i64> {
+    doc.items.iter().filter_map(|p| p.date()).max()
+}
+
+fn link(href: String, rel: &str, mime_type: &str) -> atom_syndication::Link {
+    atom_syndication::Link {
+        href,
+        rel: rel.to_string(),
+        mime_type: Some(mime_type.to_string()),
+        ..Default::default()
+    }
+}
+
+// absolute url of a page on this server, as the client reached it
+fn page(req: &HttpRequest, segments: &[&str]) -> String {
+    let info = req.connection_info();
+    let mut url = match Url::parse(&format!("{}://{}/", info.scheme(), info.host())) {
+        Ok(url) => url,
+        Err(_) => return format!("/{}", segments.join("/")),
+    };
+    if let Ok(mut path) = url.path_segments_mut() {
+        path.pop_if_empty().extend(segments);
+    }
+    url.to_string()
+}
+
+fn fixed(secs: i64) -> FixedDateTime {
+    DateTime::from_timestamp(secs, 0)
+        .unwrap_or_default()
+        .fixed_offset()
+}